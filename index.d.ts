@@ -1,15 +1,37 @@
+/** Integer compressor parameters; `compressors()` lists which each id takes. */
+export type CompressorParams = Partial<
+  Record<"level" | "quality" | "q" | "lgwin" | "accel" | "hc" | "order" | "o" | "mem", number>
+>;
+
+/** NCD variant settings; omitted fields use the core defaults. */
+export type NcdOptions = {
+  join?: "frame64" | "concat" | `sep${string}` | `interleave${string}`;
+  symmetry?: "none" | "min" | "avg" | "max";
+  metric?: "ncd" | "cdm" | "clm";
+  clamp?: boolean;
+  /** Compressor spec, e.g. "zstd", "zstd:level=19" or { id: "zstd", level: 19 }; overrides gzipLevel. */
+  compressor?: string | ({ id: string } & CompressorParams);
+};
+
 export function ncd(
   x: Buffer | Uint8Array,
   y: Buffer | Uint8Array,
   gzipLevel?: number,
+  opts?: NcdOptions,
 ): number;
 
 export function matrix(
   a: Array<Buffer | Uint8Array>,
   b: Array<Buffer | Uint8Array>,
   gzipLevel?: number,
+  opts?: NcdOptions,
 ): number[][];
 
+/** A compressor id and its tunable parameters with their defaults. */
+export type CompressorInfo = { id: string; params: Record<string, number> };
+
+export function compressors(): CompressorInfo[];
+
 export function ncdAuto(
   x: Buffer | Uint8Array,
   y: Buffer | Uint8Array,
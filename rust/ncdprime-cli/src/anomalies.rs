@@ -0,0 +1,102 @@
+use anyhow::Result;
+use clap::Args;
+use ncdprime_core::{AnomalyOptions, CompressorSpec, MatrixOptions, Threshold};
+use std::io::{self, BufWriter, Write};
+
+use crate::sequence::SequenceInput;
+use crate::{NcdArgs, OutputFormat, cache, matrix};
+
+/// Flags for `anomalies`.
+#[derive(Args, Debug)]
+pub struct AnomaliesArgs {
+    #[command(flatten)]
+    input: SequenceInput,
+    /// Preceding items each item is compared against
+    #[arg(long, default_value_t = 8)]
+    baseline: usize,
+    /// How distances are scored against recent ones (zscore|mad)
+    #[arg(long, default_value = "zscore")]
+    threshold: Threshold,
+    /// Flag items scoring above this
+    #[arg(long, default_value_t = 3.0)]
+    cutoff: f64,
+    /// Print only the flagged items
+    #[arg(long, default_value_t = false)]
+    only_anomalies: bool,
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Tsv)]
+    format: OutputFormat,
+    /// Compressor spec, e.g. `gzip`, `zstd:level=19`, `brotli:q=11,lgwin=24`
+    #[arg(long, default_value = "gzip")]
+    compressor: CompressorSpec,
+    /// Worker threads (0 = all available cores)
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+    #[command(flatten)]
+    ncd: NcdArgs,
+    #[command(flatten)]
+    cache: cache::CacheArgs,
+}
+
+/// Print every item's baseline distance and score, flagging anomalies.
+pub fn run(args: AnomaliesArgs) -> Result<()> {
+    let opts = args.ncd.options()?;
+    let set = args.input.load()?;
+    let (labels, items) = args.input.items(&set)?;
+
+    let mut scores = cache::with_compressor(&args.compressor, &args.cache, |c| {
+        ncdprime_core::ncd_anomalies(
+            c,
+            &items,
+            opts,
+            &AnomalyOptions {
+                baseline: args.baseline,
+                threshold: args.threshold,
+                cutoff: args.cutoff,
+            },
+            &MatrixOptions {
+                threads: args.threads,
+            },
+        )
+    })?;
+    // Item 0 has no baseline, so only the scored items count.
+    let scored = scores.len();
+    if args.only_anomalies {
+        scores.retain(|s| s.anomalous);
+    }
+    let flagged = scores.iter().filter(|s| s.anomalous).count();
+    eprintln!("anomalies: {flagged} of {scored} items flagged");
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    if args.format == OutputFormat::Json {
+        writeln!(out, "[")?;
+        for (k, s) in scores.iter().enumerate() {
+            let comma = if k + 1 < scores.len() { "," } else { "" };
+            writeln!(
+                out,
+                "  {{\"index\": {}, \"item\": {}, \"distance\": {}, \"score\": {}, \"anomalous\": {}}}{comma}",
+                s.index,
+                matrix::json_string(&labels[s.index]),
+                s.distance,
+                s.score,
+                s.anomalous
+            )?;
+        }
+        writeln!(out, "]")?;
+    } else {
+        writeln!(out, "index\titem\tdistance\tscore\tanomalous")?;
+        for s in &scores {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{:.3}\t{}",
+                s.index,
+                labels[s.index],
+                s.distance,
+                s.score,
+                u8::from(s.anomalous)
+            )?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
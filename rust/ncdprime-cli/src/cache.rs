@@ -0,0 +1,93 @@
+use anyhow::{Context, Result, anyhow};
+use clap::{Args, Subcommand};
+use ncdprime_core::{CachedCompressor, Compressor, CompressorSpec, NcdResult, SizeCache};
+use std::path::PathBuf;
+
+/// Persistent size-cache flags shared by `pair` and `matrix`.
+#[derive(Args, Debug, Clone)]
+pub struct CacheArgs {
+    /// Reuse compressed sizes from the persistent cache (and record new ones)
+    #[arg(long, default_value_t = false)]
+    pub cache: bool,
+    /// Cache file (default: $NCDPRIME_CACHE or ~/.cache/ncdprime/sizes.bin); implies --cache
+    #[arg(long)]
+    pub cache_path: Option<PathBuf>,
+    /// Evict the oldest entries beyond this many when saving
+    #[arg(long)]
+    pub cache_max_entries: Option<usize>,
+}
+
+impl CacheArgs {
+    /// Open the cache if it was requested.
+    pub fn open(&self) -> Result<Option<SizeCache>> {
+        if !self.cache && self.cache_path.is_none() {
+            return Ok(None);
+        }
+        let path = resolve_path(self.cache_path.clone())?;
+        let cache = SizeCache::open(&path, self.cache_max_entries)
+            .with_context(|| format!("open cache {}", path.display()))?;
+        Ok(Some(cache))
+    }
+}
+
+/// Run `f` with the compressor `spec` builds, routed through the size cache
+/// when one was requested. The cache is saved even if `f` fails, so whatever
+/// was computed is kept.
+pub fn with_compressor<T>(
+    spec: &CompressorSpec,
+    cache: &CacheArgs,
+    f: impl FnOnce(&dyn Compressor) -> NcdResult<T>,
+) -> Result<T> {
+    let c = spec.build();
+    let Some(sc) = cache.open()? else {
+        return Ok(f(&*c)?);
+    };
+    let out = f(&CachedCompressor::new(&*c, spec, &sc));
+    sc.save()?;
+    Ok(out?)
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+    /// Print entry count and file size
+    Stats {
+        #[arg(long)]
+        path: Option<PathBuf>,
+    },
+    /// Delete every cached size
+    Clear {
+        #[arg(long)]
+        path: Option<PathBuf>,
+    },
+}
+
+pub fn run(cmd: CacheCommand) -> Result<()> {
+    match cmd {
+        CacheCommand::Stats { path } => {
+            let path = resolve_path(path)?;
+            let cache = SizeCache::open(&path, None)
+                .with_context(|| format!("open cache {}", path.display()))?;
+            let st = cache.stats();
+            println!("path\t{}", path.display());
+            println!("entries\t{}", st.entries);
+            println!("file_bytes\t{}", st.file_bytes);
+        }
+        CacheCommand::Clear { path } => {
+            let path = resolve_path(path)?;
+            // Remove the file directly so even an unreadable cache can be cleared.
+            match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(e).with_context(|| format!("clear cache {}", path.display()));
+                }
+                _ => {}
+            }
+            eprintln!("cleared {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+fn resolve_path(path: Option<PathBuf>) -> Result<PathBuf> {
+    path.or_else(SizeCache::default_path)
+        .ok_or_else(|| anyhow!("no cache path: set --cache-path or $NCDPRIME_CACHE"))
+}
@@ -0,0 +1,210 @@
+use anyhow::{Context, Result, anyhow, bail};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::inputs::InputSet;
+use ncdprime_core::NcdOptions;
+
+const FORMAT_LINE: &str = "ncdprime-checkpoint 1";
+
+/// Everything a resumed run must agree on before reusing completed cells.
+///
+/// Serialized as the checkpoint header:
+///
+/// ```text
+/// ncdprime-checkpoint 1
+/// spec <compressor spec>
+/// options join=<join> symmetry=<symmetry> metric=<metric> clamp=<bool>
+/// a <blake3 of set A> <items>
+/// b <blake3 of set B> <items>
+/// ```
+///
+/// followed by one `i<TAB>j<TAB>f64-bits-hex` line per completed cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointHeader {
+    pub spec: String,
+    pub options: String,
+    pub a: (String, usize),
+    pub b: (String, usize),
+}
+
+impl CheckpointHeader {
+    pub fn new(spec: String, opts: &NcdOptions, a: &InputSet, b: &InputSet) -> Self {
+        Self {
+            spec,
+            options: options_line(opts),
+            a: (fingerprint(a), a.items.len()),
+            b: (fingerprint(b), b.items.len()),
+        }
+    }
+
+    fn render(&self) -> String {
+        format!(
+            "{FORMAT_LINE}\nspec {}\noptions {}\na {} {}\nb {} {}\n",
+            self.spec, self.options, self.a.0, self.a.1, self.b.0, self.b.1
+        )
+    }
+
+    /// Explain the first mismatch against `other`, if any.
+    fn mismatch(&self, other: &Self) -> Option<String> {
+        if self.spec != other.spec {
+            return Some(format!("compressor {} != {}", other.spec, self.spec));
+        }
+        if self.options != other.options {
+            return Some(format!("options {} != {}", other.options, self.options));
+        }
+        if self.a != other.a {
+            return Some("set A changed".to_string());
+        }
+        if self.b != other.b {
+            return Some("set B changed".to_string());
+        }
+        None
+    }
+}
+
+/// The options by their user-facing names, which stay stable as `NcdOptions`
+/// gains fields.
+fn options_line(opts: &NcdOptions) -> String {
+    format!(
+        "join={} symmetry={} metric={} clamp={}",
+        opts.join, opts.symmetry, opts.metric, opts.clamp_0_1
+    )
+}
+
+/// blake3 over every item's label and content, in order.
+pub fn fingerprint(set: &InputSet) -> String {
+    let mut h = blake3::Hasher::new();
+    h.update(&(set.items.len() as u64).to_le_bytes());
+    for item in &set.items {
+        h.update(&(item.label.len() as u64).to_le_bytes());
+        h.update(item.label.as_bytes());
+        h.update(blake3::hash(&item.bytes).as_bytes());
+    }
+    h.finalize().to_hex().to_string()
+}
+
+/// Load the completed cells of a checkpoint, verifying it was written for `expected`.
+pub fn load(path: &Path, expected: &CheckpointHeader) -> Result<HashMap<(usize, usize), f64>> {
+    let raw =
+        fs::read_to_string(path).with_context(|| format!("read checkpoint {}", path.display()))?;
+    let mut lines = raw.lines();
+
+    if lines.next() != Some(FORMAT_LINE) {
+        bail!("{} is not an ncdprime checkpoint", path.display());
+    }
+    let mut field = |name: &str| -> Result<String> {
+        lines
+            .next()
+            .and_then(|l| l.strip_prefix(name))
+            .and_then(|l| l.strip_prefix(' '))
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("checkpoint header is missing `{name}`"))
+    };
+    let set = |v: String| -> Result<(String, usize)> {
+        let (hash, n) = v.split_once(' ').ok_or_else(|| anyhow!("bad set line"))?;
+        Ok((hash.to_string(), n.parse()?))
+    };
+    let found = CheckpointHeader {
+        spec: field("spec")?,
+        options: field("options")?,
+        a: set(field("a")?)?,
+        b: set(field("b")?)?,
+    };
+    if let Some(why) = expected.mismatch(&found) {
+        bail!("checkpoint does not match this run: {why}");
+    }
+
+    let mut cells = HashMap::new();
+    for line in lines {
+        // A torn final line (crash mid-write) just means that cell is recomputed.
+        let mut parts = line.split('\t');
+        let (Some(i), Some(j), Some(bits), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        if bits.len() != 16 {
+            continue;
+        }
+        let (Ok(i), Ok(j), Ok(bits)) = (i.parse(), j.parse(), u64::from_str_radix(bits, 16)) else {
+            continue;
+        };
+        if i < found.a.1 && j < found.b.1 {
+            cells.insert((i, j), f64::from_bits(bits));
+        }
+    }
+    Ok(cells)
+}
+
+/// Appends completed cells to a checkpoint file, flushing periodically.
+pub struct CheckpointWriter {
+    path: PathBuf,
+    out: BufWriter<fs::File>,
+    every: Duration,
+    last_flush: Instant,
+}
+
+impl CheckpointWriter {
+    /// Start a checkpoint holding `header` and any already-`known` cells.
+    ///
+    /// The file is rewritten from scratch, which also drops a torn trailing line
+    /// left by an interrupted run before new cells are appended. Unless
+    /// `resuming`, an existing file at `path` is an error rather than being
+    /// replaced, since it may hold hours of completed cells.
+    pub fn create(
+        path: &Path,
+        header: &CheckpointHeader,
+        known: &HashMap<(usize, usize), f64>,
+        every: Duration,
+        resuming: bool,
+    ) -> Result<Self> {
+        if !resuming && path.exists() {
+            bail!(
+                "checkpoint {} already exists; continue it with --resume or remove it",
+                path.display()
+            );
+        }
+        let tmp = path.with_extension("tmp");
+        {
+            let mut out = BufWriter::new(
+                fs::File::create(&tmp).with_context(|| format!("create {}", tmp.display()))?,
+            );
+            out.write_all(header.render().as_bytes())?;
+            let mut cells: Vec<_> = known.iter().collect();
+            cells.sort_by_key(|(k, _)| **k);
+            for (&(i, j), d) in cells {
+                writeln!(out, "{i}\t{j}\t{:016x}", d.to_bits())?;
+            }
+            out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        }
+        fs::rename(&tmp, path).with_context(|| format!("write checkpoint {}", path.display()))?;
+
+        let file = fs::OpenOptions::new().append(true).open(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            out: BufWriter::new(file),
+            every,
+            last_flush: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, i: usize, j: usize, d: f64) -> Result<()> {
+        writeln!(self.out, "{i}\t{j}\t{:016x}", d.to_bits())?;
+        if self.last_flush.elapsed() >= self.every {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.out
+            .flush()
+            .with_context(|| format!("write checkpoint {}", self.path.display()))?;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+}
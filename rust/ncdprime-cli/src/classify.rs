@@ -0,0 +1,160 @@
+use anyhow::Result;
+use clap::Args;
+use ncdprime_core::{CompressorSpec, Confusion, KnnOptions, MatrixOptions, TieBreak, Vote};
+use std::io::{self, BufWriter, Write};
+
+use crate::inputs::LabelledSet;
+use crate::{NcdArgs, cache, inputs};
+
+/// k-NN flags shared by `classify` and `evaluate`.
+#[derive(Args, Debug)]
+pub struct KnnArgs {
+    /// Number of nearest training items that vote
+    #[arg(long, short = 'k', default_value_t = 3)]
+    k: usize,
+    /// How neighbors vote (majority|distance)
+    #[arg(long, default_value = "majority")]
+    vote: Vote,
+    /// How a tied vote is resolved (nearest|shrink|first)
+    #[arg(long, default_value = "nearest")]
+    tie_break: TieBreak,
+    /// Compressor spec, e.g. `gzip`, `zstd:level=19`, `brotli:q=11,lgwin=24`
+    #[arg(long, default_value = "gzip")]
+    compressor: CompressorSpec,
+    /// Worker threads (0 = all available cores)
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+    #[command(flatten)]
+    ncd: NcdArgs,
+    #[command(flatten)]
+    cache: cache::CacheArgs,
+}
+
+impl KnnArgs {
+    /// Predict a class of `train` for each of `test`.
+    fn predict(&self, train: &LabelledSet, test: &[Vec<u8>]) -> Result<Vec<usize>> {
+        let opts = self.ncd.options()?;
+        let train_bytes: Vec<Vec<u8>> = train.set.items.iter().map(|i| i.bytes.clone()).collect();
+
+        cache::with_compressor(&self.compressor, &self.cache, |c| {
+            ncdprime_core::ncd_classify(
+                c,
+                &train_bytes,
+                &train.labels,
+                test,
+                opts,
+                &KnnOptions {
+                    k: self.k,
+                    vote: self.vote,
+                    tie_break: self.tie_break,
+                },
+                &MatrixOptions {
+                    threads: self.threads,
+                },
+            )
+        })
+    }
+}
+
+/// Flags for `classify`.
+#[derive(Args, Debug)]
+pub struct ClassifyArgs {
+    /// Training set: a directory with one subdirectory per class, or a
+    /// manifest with --manifest
+    train: String,
+    /// Items to classify (dir, file, list file with --list, or literal)
+    test: String,
+    /// Read the training set from a `path<TAB>class` manifest
+    #[arg(long, default_value_t = false)]
+    manifest: bool,
+    /// Interpret the test arg as a newline-separated file-list file
+    #[arg(long, default_value_t = false)]
+    list: bool,
+    #[command(flatten)]
+    knn: KnnArgs,
+}
+
+/// Print `item<TAB>class` for every test item.
+pub fn run_classify(args: ClassifyArgs) -> Result<()> {
+    let train = inputs::load_labelled(&args.train, args.manifest)?;
+    let test = inputs::load_set(&inputs::auto_detect_set_spec(&args.test, args.list)?)?;
+    let test_bytes: Vec<Vec<u8>> = test.items.iter().map(|i| i.bytes.clone()).collect();
+    let predicted = args.knn.predict(&train, &test_bytes)?;
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    writeln!(out, "item\tclass")?;
+    for (item, &class) in test.items.iter().zip(&predicted) {
+        writeln!(out, "{}\t{}", item.label, train.classes[class])?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Flags for `evaluate`.
+#[derive(Args, Debug)]
+pub struct EvaluateArgs {
+    /// Training set: a directory with one subdirectory per class, or a
+    /// manifest with --manifest
+    train: String,
+    /// Labelled test set, in the same form as the training set
+    test: String,
+    /// Read both sets from `path<TAB>class` manifests
+    #[arg(long, default_value_t = false)]
+    manifest: bool,
+    #[command(flatten)]
+    knn: KnnArgs,
+}
+
+/// Classify a labelled test set and print accuracy, per-class precision and
+/// recall, and the confusion matrix (rows = actual, columns = predicted).
+pub fn run_evaluate(args: EvaluateArgs) -> Result<()> {
+    let train = inputs::load_labelled(&args.train, args.manifest)?;
+    let test = inputs::load_labelled(&args.test, args.manifest)?;
+    let test_bytes: Vec<Vec<u8>> = test.set.items.iter().map(|i| i.bytes.clone()).collect();
+    let predicted = args.knn.predict(&train, &test_bytes)?;
+
+    // Report over every class seen in either set.
+    let mut classes = train.classes.clone();
+    classes.extend(test.classes.iter().cloned());
+    classes.sort();
+    classes.dedup();
+    let index = |name: &String| classes.binary_search(name).expect("collected above");
+    let actual: Vec<usize> = test
+        .labels
+        .iter()
+        .map(|&c| index(&test.classes[c]))
+        .collect();
+    let predicted: Vec<usize> = predicted
+        .iter()
+        .map(|&c| index(&train.classes[c]))
+        .collect();
+    let m = Confusion::from_predictions(classes.len(), &actual, &predicted);
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    writeln!(
+        out,
+        "accuracy\t{:.4}\t({}/{})",
+        m.accuracy(),
+        m.correct(),
+        m.total()
+    )?;
+    writeln!(out)?;
+    writeln!(out, "class\tprecision\trecall\tsupport")?;
+    for (c, name) in classes.iter().enumerate() {
+        writeln!(
+            out,
+            "{name}\t{:.4}\t{:.4}\t{}",
+            m.precision(c),
+            m.recall(c),
+            m.support(c)
+        )?;
+    }
+    writeln!(out)?;
+    writeln!(out, "actual\\predicted\t{}", classes.join("\t"))?;
+    for (name, row) in classes.iter().zip(&m.counts) {
+        let row: Vec<String> = row.iter().map(|n| n.to_string()).collect();
+        writeln!(out, "{name}\t{}", row.join("\t"))?;
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use ncdprime_core::{CompressorSpec, MatrixOptions};
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::{NcdArgs, cache, inputs, matrix};
+
+/// Flags for `compare`, which runs the `matrix` computation once per compressor.
+#[derive(Args, Debug)]
+pub struct CompareArgs {
+    set_a: String,
+    set_b: Option<String>,
+    /// Compressor spec; repeat for each compressor to compare
+    #[arg(long = "compressor", short = 'c', required = true)]
+    compressors: Vec<CompressorSpec>,
+    /// Directory to write the bundle into (created if missing)
+    #[arg(long)]
+    out: PathBuf,
+    /// Compare set_a against itself (symmetric pairs are computed once; past 2048
+    /// items, only within blocks of rows)
+    #[arg(long, default_value_t = false)]
+    square: bool,
+    /// Interpret set args as newline-separated file-list files
+    #[arg(long, default_value_t = false)]
+    list: bool,
+    /// Matrix file format (tsv|csv)
+    #[arg(long, default_value = "tsv")]
+    format: String,
+    /// Omit row/column labels
+    #[arg(long = "no-labels", default_value_t = false)]
+    no_labels: bool,
+    /// Worker threads per compressor (0 = all available cores)
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+    #[command(flatten)]
+    ncd: NcdArgs,
+    #[command(flatten)]
+    cache: cache::CacheArgs,
+}
+
+/// Write a bundle: one matrix file per compressor plus `index.tsv`, which lists
+/// each compressor spec, its matrix file and the seconds it took.
+pub fn run(args: CompareArgs) -> Result<()> {
+    let opts = args.ncd.options()?;
+    for (k, spec) in args.compressors.iter().enumerate() {
+        if args.compressors[..k].contains(spec) {
+            bail!("compressor {spec} is listed twice");
+        }
+    }
+
+    let (a, b) = inputs::load_sets(&args.set_a, args.set_b.as_deref(), args.square, args.list)?;
+    let a_bytes: Vec<Vec<u8>> = a.items.iter().map(|i| i.bytes.clone()).collect();
+    let b_bytes: Vec<Vec<u8>> = b.items.iter().map(|i| i.bytes.clone()).collect();
+    let size_cache = args.cache.open()?;
+
+    fs::create_dir_all(&args.out).with_context(|| format!("create {}", args.out.display()))?;
+    let format = if args.format == "csv" { "csv" } else { "tsv" };
+    let (rows, cols) = matrix::rows_cols(&a, &b);
+
+    // Every matrix file is opened up front and written a row at a time as
+    // its compressor gets through the set.
+    let mut names = Vec::with_capacity(args.compressors.len());
+    let mut writers = Vec::with_capacity(args.compressors.len());
+    for (k, spec) in args.compressors.iter().enumerate() {
+        let name = format!("{:02}-{}.{format}", k + 1, spec.id());
+        let path = args.out.join(&name);
+        let file = fs::File::create(&path).with_context(|| format!("create {}", path.display()))?;
+        let mut writer = matrix::MatrixWriter::new(BufWriter::new(file), format, !args.no_labels);
+        writer.header(&cols)?;
+        names.push(name);
+        writers.push(writer);
+    }
+
+    let started = Instant::now();
+    let elapsed = ncdprime_core::ncd_matrix_multi_rows(
+        &args.compressors,
+        &a_bytes,
+        &b_bytes,
+        opts,
+        &MatrixOptions {
+            threads: args.threads,
+        },
+        size_cache.as_ref(),
+        |k, p| {
+            if p.done == p.total || p.done % 200 == 0 {
+                eprintln!(
+                    "compare: {} {}/{} (elapsed={:.1}s)",
+                    args.compressors[k],
+                    p.done,
+                    p.total,
+                    started.elapsed().as_secs_f64()
+                );
+            }
+        },
+        |k, i, values| Ok(writers[k].row(&rows[i], &values)?),
+    );
+    if let Some(sc) = &size_cache {
+        sc.save()?;
+    }
+    let elapsed = elapsed?;
+
+    let mut index = String::from("compressor\tfile\tseconds\n");
+    for ((spec, name), secs) in args.compressors.iter().zip(&names).zip(&elapsed) {
+        let secs = secs.as_secs_f64();
+        index.push_str(&format!("{spec}\t{name}\t{secs:.6}\n"));
+        eprintln!("compare: {spec} took {secs:.3}s -> {name}");
+    }
+
+    let index_path = args.out.join("index.tsv");
+    fs::File::create(&index_path)
+        .and_then(|mut f| f.write_all(index.as_bytes()))
+        .with_context(|| format!("write {}", index_path.display()))?;
+    Ok(())
+}
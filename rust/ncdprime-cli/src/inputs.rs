@@ -50,6 +50,23 @@ pub fn auto_detect_set_spec(arg: &str, list: bool) -> Result<SetSpec> {
     })
 }
 
+/// Load the row and column sets of a matrix run; `square` compares `set_a`
+/// against itself and ignores `set_b`, as does a missing `set_b`.
+pub fn load_sets(
+    set_a: &str,
+    set_b: Option<&str>,
+    square: bool,
+    list: bool,
+) -> Result<(InputSet, InputSet)> {
+    let spec_a = auto_detect_set_spec(set_a, list)?;
+    let spec_b = if square {
+        spec_a.clone()
+    } else {
+        auto_detect_set_spec(set_b.unwrap_or(set_a), list)?
+    };
+    Ok((load_set(&spec_a)?, load_set(&spec_b)?))
+}
+
 pub fn load_set(spec: &SetSpec) -> Result<InputSet> {
     match spec {
         SetSpec::Dir { path } => {
@@ -136,3 +153,85 @@ pub fn load_set(spec: &SetSpec) -> Result<InputSet> {
         }
     }
 }
+
+/// A set whose items each belong to a class, for `classify` and `evaluate`.
+#[derive(Debug, Clone)]
+pub struct LabelledSet {
+    pub set: InputSet,
+    /// Class names, sorted.
+    pub classes: Vec<String>,
+    /// `labels[i]` indexes `classes` for `set.items[i]`.
+    pub labels: Vec<usize>,
+}
+
+impl LabelledSet {
+    fn new(name: String, items: Vec<(InputItem, String)>) -> Self {
+        let mut classes: Vec<String> = items.iter().map(|(_, c)| c.clone()).collect();
+        classes.sort();
+        classes.dedup();
+        let labels = items
+            .iter()
+            .map(|(_, c)| classes.binary_search(c).expect("collected above"))
+            .collect();
+        let items = items.into_iter().map(|(item, _)| item).collect();
+        Self {
+            set: InputSet { name, items },
+            classes,
+            labels,
+        }
+    }
+}
+
+/// Load a labelled set: either a directory with one subdirectory per class
+/// or, with `manifest`, the files it lists as `path<TAB>class` lines (relative
+/// paths are resolved against the manifest's directory).
+///
+/// Items are labelled `class/file` for directories and by their listed path
+/// for manifests, so files with the same name in two classes stay distinct.
+pub fn load_labelled(arg: &str, manifest: bool) -> Result<LabelledSet> {
+    let path = Path::new(arg);
+    let mut items = Vec::new();
+
+    if manifest {
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("read_to_string({})", path.display()))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        for (n, line) in raw.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (file, class) = line
+                .split_once('\t')
+                .ok_or_else(|| anyhow!("{}:{}: expected `path<TAB>class`", arg, n + 1))?;
+            let file_path = base.join(file);
+            let bytes =
+                fs::read(&file_path).with_context(|| format!("read({})", file_path.display()))?;
+            let item = InputItem {
+                label: file.to_string(),
+                bytes,
+            };
+            items.push((item, class.trim().to_string()));
+        }
+    } else {
+        let mut dirs: Vec<_> = fs::read_dir(path)
+            .with_context(|| format!("read_dir({})", path.display()))?
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().map(|ft| ft.is_dir()).unwrap_or(false))
+            .collect();
+        dirs.sort_by_key(|e| e.file_name());
+        for dir in dirs {
+            let class = dir.file_name().to_string_lossy().into_owned();
+            let set = load_set(&SetSpec::Dir { path: dir.path() })?;
+            for mut item in set.items {
+                item.label = format!("{class}/{}", item.label);
+                items.push((item, class.clone()));
+            }
+        }
+    }
+
+    if items.is_empty() {
+        return Err(anyhow!("no labelled items in {arg}"));
+    }
+    Ok(LabelledSet::new(path.display().to_string(), items))
+}
@@ -1,8 +1,19 @@
+mod anomalies;
+mod cache;
+mod checkpoint;
+mod classify;
+mod compare;
 mod inputs;
 mod matrix;
+mod nearest;
+mod sequence;
+mod tree;
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::collections::HashMap;
 use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser, Debug)]
 #[command(name = "ncdprime")]
@@ -12,35 +23,109 @@ struct Cli {
     command: Commands,
 }
 
+/// NCD variant flags shared by `pair` and `matrix`.
+#[derive(Args, Debug, Clone)]
+struct NcdArgs {
+    /// How x and y are joined before compressing C(xy)
+    /// (frame64|concat|sep[:HEX]|interleave[:BYTES])
+    #[arg(long, default_value = "frame64")]
+    join: String,
+    /// How C(xy) and C(yx) are combined (none|min|avg|max)
+    #[arg(long, default_value = "min")]
+    symmetry: String,
+    /// Distance computed from the sizes (ncd|cdm|clm)
+    #[arg(long, default_value = "ncd")]
+    metric: String,
+    /// Clamp distances to [0, 1]
+    #[arg(long, default_value_t = false)]
+    clamp: bool,
+}
+
+impl NcdArgs {
+    fn options(&self) -> ncdprime_core::NcdResult<ncdprime_core::NcdOptions> {
+        ncdprime_core::NcdOptions::from_names(
+            Some(&self.join),
+            Some(&self.symmetry),
+            Some(&self.metric),
+            Some(self.clamp),
+        )
+    }
+}
+
+/// Output format of the commands that print records rather than a matrix.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    Tsv,
+    Json,
+}
+
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Compute NCD between two files
     Pair {
         file_a: String,
         file_b: String,
+        /// Compressor spec, e.g. `gzip`, `zstd:level=19`, `brotli:q=11,lgwin=24`
         #[arg(long, default_value = "gzip")]
-        compressor: String,
-        #[arg(long, default_value_t = 9)]
-        gzip_level: u32,
-        #[arg(long, default_value_t = 3)]
-        zstd_level: i32,
-        #[arg(long, default_value_t = 11)]
-        brotli_quality: u32,
-        #[arg(long, default_value_t = 22)]
-        brotli_lgwin: u32,
-        #[arg(long, default_value_t = 1)]
-        lz4_accel: i32,
-        #[arg(long, default_value_t = 6)]
-        xz_level: u32,
+        compressor: ncdprime_core::CompressorSpec,
+        #[command(flatten)]
+        ncd: NcdArgs,
+        #[command(flatten)]
+        cache: cache::CacheArgs,
+        /// Also print C(x), C(y), C(xy), C(yx), the join direction used and the wall time
+        #[arg(long, default_value_t = false)]
+        explain: bool,
     },
 
     /// List available compressors
     Compressors,
 
+    /// Train a zstd dictionary from samples, for `--compressor zstd-dict:dict=PATH`
+    TrainDict {
+        /// Sample set: a directory, file-list (with --list) or file; one sample per file
+        samples: String,
+        /// Interpret the samples arg as a newline-separated file-list file
+        #[arg(long, default_value_t = false)]
+        list: bool,
+        /// Where to write the dictionary
+        #[arg(long)]
+        out: PathBuf,
+        /// Maximum dictionary size in bytes
+        #[arg(long, default_value_t = ncdprime_core::DEFAULT_DICT_SIZE)]
+        max_size: usize,
+    },
+
+    /// Inspect or clear the persistent compressed-size cache
+    #[command(subcommand)]
+    Cache(cache::CacheCommand),
+
+    /// Compute one matrix per compressor over the same sets and write them as a bundle
+    Compare(compare::CompareArgs),
+
+    /// Classify items by k-NN over NCD to a labelled training set
+    Classify(classify::ClassifyArgs),
+
+    /// Classify a labelled test set and report accuracy, precision/recall and confusion
+    Evaluate(classify::EvaluateArgs),
+
+    /// Find the k nearest references to each query item
+    Nearest(nearest::NearestArgs),
+
+    /// Flag items unusually far from the items just before them
+    Anomalies(anomalies::AnomaliesArgs),
+
+    /// NCD between consecutive items of an ordered set, or windows of one file
+    Sequence(sequence::SequenceArgs),
+
+    /// Cluster a set by NCD and print the tree in Newick format
+    Tree(tree::TreeArgs),
+
     /// Compute an NCD matrix between two sets (dirs, files, list files, or literals).
     Matrix {
         set_a: String,
         set_b: Option<String>,
+        /// Compare set_a against itself (symmetric pairs are computed once; past 2048
+        /// items, only within blocks of rows)
         #[arg(long, default_value_t = false)]
         square: bool,
         /// Interpret set args as newline-separated file-list files
@@ -52,20 +137,30 @@ enum Commands {
         /// Omit row/column labels
         #[arg(long = "no-labels", default_value_t = false)]
         no_labels: bool,
+        /// Worker threads (0 = all available cores)
+        #[arg(long, default_value_t = 0)]
+        threads: usize,
+        /// Record completed cells in this checkpoint file (which must not exist yet
+        /// unless --resume is given)
+        #[arg(long)]
+        checkpoint: Option<PathBuf>,
+        /// Seconds between checkpoint flushes
+        #[arg(long, default_value_t = 30)]
+        checkpoint_every: u64,
+        /// Continue a run from its checkpoint (inputs must be unchanged); new cells are appended to it
+        #[arg(long)]
+        resume: Option<PathBuf>,
+        /// Write the compressed sizes behind every computed cell to this TSV file
+        /// (cells restored by --resume are not recompressed and not listed)
+        #[arg(long)]
+        sizes_out: Option<PathBuf>,
+        /// Compressor spec, e.g. `gzip`, `zstd:level=19`, `brotli:q=11,lgwin=24`
         #[arg(long, default_value = "gzip")]
-        compressor: String,
-        #[arg(long, default_value_t = 9)]
-        gzip_level: u32,
-        #[arg(long, default_value_t = 3)]
-        zstd_level: i32,
-        #[arg(long, default_value_t = 11)]
-        brotli_quality: u32,
-        #[arg(long, default_value_t = 22)]
-        brotli_lgwin: u32,
-        #[arg(long, default_value_t = 1)]
-        lz4_accel: i32,
-        #[arg(long, default_value_t = 6)]
-        xz_level: u32,
+        compressor: ncdprime_core::CompressorSpec,
+        #[command(flatten)]
+        ncd: NcdArgs,
+        #[command(flatten)]
+        cache: cache::CacheArgs,
     },
 }
 
@@ -79,31 +174,64 @@ fn main() -> anyhow::Result<()> {
             }
         }
 
+        Commands::Cache(cmd) => cache::run(cmd)?,
+
+        Commands::TrainDict {
+            samples,
+            list,
+            out,
+            max_size,
+        } => {
+            let set = inputs::load_set(&inputs::auto_detect_set_spec(&samples, list)?)?;
+            let samples: Vec<&[u8]> = set.items.iter().map(|i| i.bytes.as_slice()).collect();
+            let dict = ncdprime_core::train_zstd_dictionary(&samples, max_size)?;
+            fs::write(&out, &dict)?;
+            eprintln!(
+                "train-dict: {} bytes from {} samples -> {}",
+                dict.len(),
+                samples.len(),
+                out.display()
+            );
+        }
+
+        Commands::Compare(args) => compare::run(args)?,
+
+        Commands::Classify(args) => classify::run_classify(args)?,
+
+        Commands::Evaluate(args) => classify::run_evaluate(args)?,
+
+        Commands::Nearest(args) => nearest::run(args)?,
+
+        Commands::Anomalies(args) => anomalies::run(args)?,
+
+        Commands::Sequence(args) => sequence::run(args)?,
+
+        Commands::Tree(args) => tree::run(args)?,
+
         Commands::Pair {
             file_a,
             file_b,
-            compressor,
-            gzip_level,
-            zstd_level,
-            brotli_quality,
-            brotli_lgwin,
-            lz4_accel,
-            xz_level,
+            compressor: spec,
+            ncd,
+            cache,
+            explain,
         } => {
+            let opts = ncd.options()?;
             let a = fs::read(file_a)?;
             let b = fs::read(file_b)?;
-            let spec = ncdprime_core::parse_compressor(
-                &compressor,
-                gzip_level,
-                zstd_level,
-                brotli_quality,
-                brotli_lgwin,
-                lz4_accel,
-                xz_level,
-            )?;
-            let c = spec.build();
-            let d = ncdprime_core::ncd(&*c, &a, &b, ncdprime_core::NcdOptions::default())?;
-            println!("{d}");
+            let d = cache::with_compressor(&spec, &cache, |c| {
+                ncdprime_core::ncd_detail(c, &a, &b, opts)
+            })?;
+            println!("{}", d.distance);
+            if explain {
+                let cyx = d.cyx.map_or_else(|| "-".to_string(), |n| n.to_string());
+                println!("C(x)\t{}", d.cx);
+                println!("C(y)\t{}", d.cy);
+                println!("C(xy)\t{}", d.cxy);
+                println!("C(yx)\t{cyx}");
+                println!("join\t{}", d.direction);
+                println!("wall\t{:.3?}", d.wall);
+            }
         }
 
         Commands::Matrix {
@@ -113,34 +241,17 @@ fn main() -> anyhow::Result<()> {
             list,
             format,
             no_labels,
-            compressor,
-            gzip_level,
-            zstd_level,
-            brotli_quality,
-            brotli_lgwin,
-            lz4_accel,
-            xz_level,
+            threads,
+            checkpoint,
+            checkpoint_every,
+            resume,
+            sizes_out,
+            compressor: spec,
+            ncd,
+            cache,
         } => {
-            let spec_a = inputs::auto_detect_set_spec(&set_a, list)?;
-            let spec_b = if square {
-                spec_a.clone()
-            } else {
-                inputs::auto_detect_set_spec(set_b.as_deref().unwrap_or(&set_a), list)?
-            };
-
-            let a = inputs::load_set(&spec_a)?;
-            let b = inputs::load_set(&spec_b)?;
-
-            let spec = ncdprime_core::parse_compressor(
-                &compressor,
-                gzip_level,
-                zstd_level,
-                brotli_quality,
-                brotli_lgwin,
-                lz4_accel,
-                xz_level,
-            )?;
-            let c = spec.build();
+            let opts = ncd.options()?;
+            let (a, b) = inputs::load_sets(&set_a, set_b.as_deref(), square, list)?;
 
             let a_bytes: Vec<Vec<u8>> = a.items.iter().map(|i| i.bytes.clone()).collect();
             let b_bytes: Vec<Vec<u8>> = b.items.iter().map(|i| i.bytes.clone()).collect();
@@ -149,84 +260,152 @@ fn main() -> anyhow::Result<()> {
             let started = std::time::Instant::now();
             let mut bytes_seen: u128 = 0;
 
-            let values = ncdprime_core::ncd_matrix_with_progress(
-                &*c,
-                &a_bytes,
-                &b_bytes,
-                ncdprime_core::NcdOptions::default(),
-                |p| {
-                    // Keep the estimator warm.
-                    est.add(ncdprime_cli::eta::Sample {
-                        input_bytes: p.input_bytes,
-                        wall: p.wall,
-                    });
-                    bytes_seen += p.input_bytes as u128;
-
-                    // Refit early (first ~6 samples) and again around ~15 samples.
-                    // Keeping refits sparse avoids overfitting and keeps ETA stable.
-                    match est.sample_count() {
-                        6 => est.refit_first_n(6),
-                        15 | 16 => est.refit_first_n(15),
-                        _ => {}
-                    }
-
-                    // Emit occasional progress updates to stderr (so matrix output stays clean).
-                    let should_print = p.done == 1
-                        || p.done == p.total
-                        || (p.done % 200 == 0)
-                        || est.should_refit();
-                    if !should_print {
-                        return;
-                    }
-
-                    let elapsed = started.elapsed();
-                    let pct = if p.total == 0 {
-                        100.0
-                    } else {
-                        (p.done as f64) * 100.0 / (p.total as f64)
-                    };
-
-                    let remaining_cells = p.total.saturating_sub(p.done);
-                    let avg_bytes = if p.done == 0 {
-                        0u64
-                    } else {
-                        (bytes_seen / (p.done as u128)) as u64
-                    };
-                    let eta =
-                        est.estimate_remaining(std::iter::repeat_n(avg_bytes, remaining_cells));
-
-                    fn fmt_dur(d: std::time::Duration) -> String {
-                        let s = d.as_secs();
-                        let h = s / 3600;
-                        let m = (s % 3600) / 60;
-                        let ss = s % 60;
-                        if h > 0 {
-                            format!("{h}:{m:02}:{ss:02}")
-                        } else {
-                            format!("{m}:{ss:02}")
-                        }
-                    }
-
-                    let eta_str = eta.map(fmt_dur).unwrap_or_else(|| "?".to_string());
-                    eprintln!(
-                        "matrix: {}/{} ({pct:.1}%) elapsed={} eta={} (samples={})",
-                        p.done,
-                        p.total,
-                        fmt_dur(elapsed),
-                        eta_str,
-                        est.sample_count(),
-                    );
-                },
-            )?;
+            let mopts = ncdprime_core::MatrixOptions { threads };
+
+            let header = checkpoint::CheckpointHeader::new(spec.cache_key(), &opts, &a, &b);
+            let known = match &resume {
+                Some(path) => {
+                    let known = checkpoint::load(path, &header)?;
+                    eprintln!("matrix: resuming with {} completed cells", known.len());
+                    known
+                }
+                None => HashMap::new(),
+            };
+            let mut ckpt = checkpoint
+                .as_ref()
+                .or(resume.as_ref())
+                .map(|path| {
+                    checkpoint::CheckpointWriter::create(
+                        path,
+                        &header,
+                        &known,
+                        Duration::from_secs(checkpoint_every),
+                        resume.as_ref() == Some(path),
+                    )
+                })
+                .transpose()?;
+            let mut ckpt_err = None;
+            let mut sizes = sizes_out
+                .as_ref()
+                .map(|path| {
+                    matrix::SizesWriter::new(std::io::BufWriter::new(fs::File::create(path)?))
+                })
+                .transpose()?;
+            let mut sizes_err = None;
+
+            let workers = mopts.worker_threads() as u32;
+
+            // Rows are written as soon as they are finished, so partial output is usable.
             let (rows, cols) = matrix::rows_cols(&a, &b);
-            let out = matrix::format_matrix(
-                &rows,
-                &cols,
-                &values,
+            let mut writer = matrix::MatrixWriter::new(
+                std::io::stdout().lock(),
                 if format == "csv" { "csv" } else { "tsv" },
                 !no_labels,
             );
-            print!("{out}");
+            writer.header(&cols)?;
+
+            // The size cache keeps whatever was computed, even if the matrix
+            // failed part-way.
+            let result = cache::with_compressor(&spec, &cache, |c| {
+                ncdprime_core::ncd_matrix_rows_resume(
+                    c,
+                    &a_bytes,
+                    &b_bytes,
+                    opts,
+                    &mopts,
+                    &known,
+                    |p| {
+                        if let Some(w) = ckpt.as_mut()
+                            && let Err(e) = w.record(p.row, p.col, p.value)
+                        {
+                            ckpt_err.get_or_insert(e);
+                        }
+                        if let Some(w) = sizes.as_mut()
+                            && let Err(e) = w.cell(&rows[p.row], &cols[p.col], &p.detail)
+                        {
+                            sizes_err.get_or_insert(e);
+                        }
+
+                        // Keep the estimator warm.
+                        est.add(ncdprime_cli::eta::Sample {
+                            input_bytes: p.input_bytes,
+                            wall: p.wall,
+                        });
+                        bytes_seen += p.input_bytes as u128;
+
+                        // Refit early (first ~6 samples) and again around ~15 samples.
+                        // Keeping refits sparse avoids overfitting and keeps ETA stable.
+                        match est.sample_count() {
+                            6 => est.refit_first_n(6),
+                            15 | 16 => est.refit_first_n(15),
+                            _ => {}
+                        }
+
+                        // Emit occasional progress updates to stderr (so matrix output stays clean).
+                        let should_print = p.done == 1
+                            || p.done == p.total
+                            || (p.done % 200 == 0)
+                            || est.should_refit();
+                        if !should_print {
+                            return;
+                        }
+
+                        let elapsed = started.elapsed();
+                        let pct = if p.total == 0 {
+                            100.0
+                        } else {
+                            (p.done as f64) * 100.0 / (p.total as f64)
+                        };
+
+                        let remaining_cells = p.total.saturating_sub(p.done);
+                        let avg_bytes = if p.done == 0 {
+                            0u64
+                        } else {
+                            (bytes_seen / (p.done as u128)) as u64
+                        };
+                        // Samples are per-cell wall times; cells run `workers` at a time.
+                        let eta = est
+                            .estimate_remaining(std::iter::repeat_n(avg_bytes, remaining_cells))
+                            .map(|d| d / workers);
+
+                        fn fmt_dur(d: std::time::Duration) -> String {
+                            let s = d.as_secs();
+                            let h = s / 3600;
+                            let m = (s % 3600) / 60;
+                            let ss = s % 60;
+                            if h > 0 {
+                                format!("{h}:{m:02}:{ss:02}")
+                            } else {
+                                format!("{m}:{ss:02}")
+                            }
+                        }
+
+                        let eta_str = eta.map(fmt_dur).unwrap_or_else(|| "?".to_string());
+                        eprintln!(
+                            "matrix: {}/{} ({pct:.1}%) elapsed={} eta={} (samples={})",
+                            p.done,
+                            p.total,
+                            fmt_dur(elapsed),
+                            eta_str,
+                            est.sample_count(),
+                        );
+                    },
+                    |i, values| Ok(writer.row(&rows[i], &values)?),
+                )
+            });
+            if let Some(w) = ckpt.as_mut() {
+                w.flush()?;
+            }
+            if let Some(w) = sizes.as_mut() {
+                w.flush()?;
+            }
+            if let Some(e) = ckpt_err {
+                return Err(e);
+            }
+            if let Some(e) = sizes_err {
+                return Err(e.into());
+            }
+            result?;
         }
     }
 
@@ -1,42 +1,82 @@
 use crate::inputs::InputSet;
+use ncdprime_core::NcdDetail;
+use std::io::{self, Write};
 
-pub fn format_matrix(
-    rows: &[String],
-    cols: &[String],
-    values: &[Vec<f64>],
-    format: &str,
+/// Writes a labeled TSV/CSV matrix one row at a time.
+pub struct MatrixWriter<W: Write> {
+    out: W,
+    sep: char,
     labels: bool,
-) -> String {
-    let sep = if format == "csv" { ',' } else { '\t' };
+}
 
-    let mut out = String::new();
+impl<W: Write> MatrixWriter<W> {
+    pub fn new(out: W, format: &str, labels: bool) -> Self {
+        let sep = if format == "csv" { ',' } else { '\t' };
+        Self { out, sep, labels }
+    }
 
-    if labels {
-        out.push(sep);
-        out.push_str(&cols.join(&sep.to_string()));
-        out.push('\n');
+    /// Column header line (only written when labels are enabled).
+    pub fn header(&mut self, cols: &[String]) -> io::Result<()> {
+        if !self.labels {
+            return Ok(());
+        }
+        let mut line = String::new();
+        line.push(self.sep);
+        line.push_str(&cols.join(&self.sep.to_string()));
+        line.push('\n');
+        self.out.write_all(line.as_bytes())
+    }
 
-        for (r, row_name) in rows.iter().enumerate() {
-            out.push_str(row_name);
-            for v in &values[r] {
-                out.push(sep);
-                out.push_str(&v.to_string());
-            }
-            out.push('\n');
+    /// One matrix row, flushed immediately so partial output is usable.
+    pub fn row(&mut self, label: &str, values: &[f64]) -> io::Result<()> {
+        let mut line = String::new();
+        if self.labels {
+            line.push_str(label);
         }
-    } else {
-        for row in values.iter().take(rows.len()) {
-            for (j, v) in row.iter().enumerate() {
-                if j > 0 {
-                    out.push(sep);
-                }
-                out.push_str(&v.to_string());
+        for (j, v) in values.iter().enumerate() {
+            if self.labels || j > 0 {
+                line.push(self.sep);
             }
-            out.push('\n');
+            line.push_str(&v.to_string());
         }
+        line.push('\n');
+        self.out.write_all(line.as_bytes())?;
+        self.out.flush()
     }
+}
 
-    out
+/// Writes the compressed sizes behind each computed cell, one TSV line per
+/// cell in completion order, for `matrix --sizes-out`.
+pub struct SizesWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> SizesWriter<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        writeln!(
+            out,
+            "row\tcol\tC(x)\tC(y)\tC(xy)\tC(yx)\tjoin\tdistance\twall_us"
+        )?;
+        Ok(Self { out })
+    }
+
+    pub fn cell(&mut self, row: &str, col: &str, d: &NcdDetail) -> io::Result<()> {
+        let cyx = d.cyx.map_or_else(|| "-".to_string(), |n| n.to_string());
+        writeln!(
+            self.out,
+            "{row}\t{col}\t{}\t{}\t{}\t{cyx}\t{}\t{}\t{}",
+            d.cx,
+            d.cy,
+            d.cxy,
+            d.direction,
+            d.distance,
+            d.wall.as_micros()
+        )
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
 }
 
 pub fn rows_cols(a: &InputSet, b: &InputSet) -> (Vec<String>, Vec<String>) {
@@ -44,3 +84,21 @@ pub fn rows_cols(a: &InputSet, b: &InputSet) -> (Vec<String>, Vec<String>) {
     let cols = b.items.iter().map(|i| i.label.clone()).collect();
     (rows, cols)
 }
+
+/// `s` as a quoted JSON string.
+pub fn json_string(s: &str) -> String {
+    let mut out = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if u32::from(c) < 0x20 => out.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
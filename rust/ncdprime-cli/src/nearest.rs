@@ -0,0 +1,128 @@
+use anyhow::Result;
+use clap::Args;
+use ncdprime_core::{CompressorSpec, MatrixOptions, Neighbor};
+use std::io::{self, BufWriter, Write};
+use std::time::Instant;
+
+use crate::inputs::InputSet;
+use crate::{NcdArgs, OutputFormat, cache, inputs, matrix};
+
+/// Flags for `nearest`, which ranks a reference set against each query.
+#[derive(Args, Debug)]
+pub struct NearestArgs {
+    queries: String,
+    refs: String,
+    /// Number of references to report per query
+    #[arg(long, short = 'k', default_value_t = 5)]
+    k: usize,
+    /// Interpret set args as newline-separated file-list files
+    #[arg(long, default_value_t = false)]
+    list: bool,
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Tsv)]
+    format: OutputFormat,
+    /// Compressor spec, e.g. `gzip`, `zstd:level=19`, `brotli:q=11,lgwin=24`
+    #[arg(long, default_value = "gzip")]
+    compressor: CompressorSpec,
+    /// Worker threads (0 = all available cores)
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+    #[command(flatten)]
+    ncd: NcdArgs,
+    #[command(flatten)]
+    cache: cache::CacheArgs,
+}
+
+/// Print the `k` nearest references of every query, nearest first.
+pub fn run(args: NearestArgs) -> Result<()> {
+    let opts = args.ncd.options()?;
+    let (queries, refs) = inputs::load_sets(&args.queries, Some(&args.refs), false, args.list)?;
+    let q_bytes: Vec<Vec<u8>> = queries.items.iter().map(|i| i.bytes.clone()).collect();
+    let r_bytes: Vec<Vec<u8>> = refs.items.iter().map(|i| i.bytes.clone()).collect();
+
+    let started = Instant::now();
+    let nearest = cache::with_compressor(&args.compressor, &args.cache, |c| {
+        ncdprime_core::ncd_nearest_with_progress(
+            c,
+            &q_bytes,
+            &r_bytes,
+            args.k,
+            opts,
+            &MatrixOptions {
+                threads: args.threads,
+            },
+            |p| {
+                if p.done == p.total || p.done % 1000 == 0 {
+                    eprintln!(
+                        "nearest: {}/{} (elapsed={:.1}s)",
+                        p.done,
+                        p.total,
+                        started.elapsed().as_secs_f64()
+                    );
+                }
+            },
+        )
+    })?;
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    if args.format == OutputFormat::Json {
+        write_json(&mut out, &queries, &refs, &nearest)?;
+    } else {
+        write_tsv(&mut out, &queries, &refs, &nearest)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// One line per (query, neighbor), ranks starting at 1.
+fn write_tsv(
+    out: &mut impl Write,
+    queries: &InputSet,
+    refs: &InputSet,
+    nearest: &[Vec<Neighbor>],
+) -> io::Result<()> {
+    writeln!(out, "query\trank\treference\tdistance")?;
+    for (q, neighbors) in queries.items.iter().zip(nearest) {
+        for (rank, n) in neighbors.iter().enumerate() {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}",
+                q.label,
+                rank + 1,
+                refs.items[n.index].label,
+                n.distance
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// `[{"query": ..., "neighbors": [{"reference": ..., "distance": ...}]}]`.
+fn write_json(
+    out: &mut impl Write,
+    queries: &InputSet,
+    refs: &InputSet,
+    nearest: &[Vec<Neighbor>],
+) -> io::Result<()> {
+    writeln!(out, "[")?;
+    for (k, (q, neighbors)) in queries.items.iter().zip(nearest).enumerate() {
+        let list: Vec<String> = neighbors
+            .iter()
+            .map(|n| {
+                format!(
+                    "{{\"reference\": {}, \"distance\": {}}}",
+                    matrix::json_string(&refs.items[n.index].label),
+                    n.distance
+                )
+            })
+            .collect();
+        let comma = if k + 1 < nearest.len() { "," } else { "" };
+        writeln!(
+            out,
+            "  {{\"query\": {}, \"neighbors\": [{}]}}{comma}",
+            matrix::json_string(&q.label),
+            list.join(", ")
+        )?;
+    }
+    writeln!(out, "]")
+}
@@ -0,0 +1,115 @@
+use anyhow::{Result, bail};
+use clap::Args;
+use ncdprime_core::{CompressorSpec, MatrixOptions};
+use std::io::{self, BufWriter, Write};
+
+use crate::inputs::InputSet;
+use crate::{NcdArgs, OutputFormat, cache, inputs, matrix};
+
+/// Where the items of a sequence come from; shared with `anomalies`.
+#[derive(Args, Debug)]
+pub struct SequenceInput {
+    /// Ordered set (dir sorted by name, list file in listed order), or one
+    /// file to cut into windows with --window
+    set: String,
+    /// Interpret the set arg as a newline-separated file-list file
+    #[arg(long, default_value_t = false)]
+    list: bool,
+    /// Cut the (single-file) set into windows of this many bytes
+    #[arg(long)]
+    window: Option<usize>,
+    /// Bytes between window starts (default: the window size)
+    #[arg(long, requires = "window")]
+    step: Option<usize>,
+}
+
+impl SequenceInput {
+    /// Read the set's files.
+    pub fn load(&self) -> Result<InputSet> {
+        inputs::load_set(&inputs::auto_detect_set_spec(&self.set, self.list)?)
+    }
+
+    /// Item labels and contents of `set`, in sequence order. Windows are
+    /// labelled `file@offset` and borrow from the file's bytes.
+    pub fn items<'a>(&self, set: &'a InputSet) -> Result<(Vec<String>, Vec<&'a [u8]>)> {
+        let Some(window) = self.window else {
+            return Ok(set
+                .items
+                .iter()
+                .map(|i| (i.label.clone(), i.bytes.as_slice()))
+                .unzip());
+        };
+        let [item] = &set.items[..] else {
+            bail!(
+                "--window needs a single input file, got {} items",
+                set.items.len()
+            );
+        };
+        let step = self.step.unwrap_or(window);
+        let windows = ncdprime_core::sliding_windows(&item.bytes, window, step)?;
+        let labels = (0..windows.len())
+            .map(|k| format!("{}@{}", item.label, k * step))
+            .collect();
+        Ok((labels, windows))
+    }
+}
+
+/// Flags for `sequence`.
+#[derive(Args, Debug)]
+pub struct SequenceArgs {
+    #[command(flatten)]
+    input: SequenceInput,
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Tsv)]
+    format: OutputFormat,
+    /// Compressor spec, e.g. `gzip`, `zstd:level=19`, `brotli:q=11,lgwin=24`
+    #[arg(long, default_value = "gzip")]
+    compressor: CompressorSpec,
+    /// Worker threads (0 = all available cores)
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+    #[command(flatten)]
+    ncd: NcdArgs,
+    #[command(flatten)]
+    cache: cache::CacheArgs,
+}
+
+/// Print NCD(item i, item i+1) for every consecutive pair.
+pub fn run(args: SequenceArgs) -> Result<()> {
+    let opts = args.ncd.options()?;
+    let set = args.input.load()?;
+    let (labels, items) = args.input.items(&set)?;
+
+    let series = cache::with_compressor(&args.compressor, &args.cache, |c| {
+        ncdprime_core::ncd_sequence(
+            c,
+            &items,
+            opts,
+            &MatrixOptions {
+                threads: args.threads,
+            },
+        )
+    })?;
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    if args.format == OutputFormat::Json {
+        writeln!(out, "[")?;
+        for (i, d) in series.iter().enumerate() {
+            let comma = if i + 1 < series.len() { "," } else { "" };
+            writeln!(
+                out,
+                "  {{\"index\": {i}, \"from\": {}, \"to\": {}, \"distance\": {d}}}{comma}",
+                matrix::json_string(&labels[i]),
+                matrix::json_string(&labels[i + 1])
+            )?;
+        }
+        writeln!(out, "]")?;
+    } else {
+        writeln!(out, "index\tfrom\tto\tdistance")?;
+        for (i, d) in series.iter().enumerate() {
+            writeln!(out, "{i}\t{}\t{}\t{d}", labels[i], labels[i + 1])?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
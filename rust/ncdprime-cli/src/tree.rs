@@ -0,0 +1,50 @@
+use anyhow::Result;
+use clap::Args;
+use ncdprime_core::{CompressorSpec, MatrixOptions, TreeMethod};
+
+use crate::{NcdArgs, cache, inputs};
+
+/// Flags for `tree`, which clusters a set by its square NCD matrix.
+#[derive(Args, Debug)]
+pub struct TreeArgs {
+    set: String,
+    /// Interpret the set arg as a newline-separated file-list file
+    #[arg(long, default_value_t = false)]
+    list: bool,
+    /// Tree construction method (upgma|single|complete|nj); `average` is upgma
+    #[arg(long, default_value = "upgma")]
+    method: TreeMethod,
+    /// Compressor spec, e.g. `gzip`, `zstd:level=19`, `brotli:q=11,lgwin=24`
+    #[arg(long, default_value = "gzip")]
+    compressor: CompressorSpec,
+    /// Worker threads (0 = all available cores)
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+    #[command(flatten)]
+    ncd: NcdArgs,
+    #[command(flatten)]
+    cache: cache::CacheArgs,
+}
+
+/// Print the set's tree in Newick format, with leaves named by item label.
+pub fn run(args: TreeArgs) -> Result<()> {
+    let opts = args.ncd.options()?;
+    let set = inputs::load_set(&inputs::auto_detect_set_spec(&args.set, args.list)?)?;
+    let items: Vec<Vec<u8>> = set.items.iter().map(|i| i.bytes.clone()).collect();
+
+    let d = cache::with_compressor(&args.compressor, &args.cache, |c| {
+        ncdprime_core::ncd_matrix_square(
+            c,
+            &items,
+            opts,
+            &MatrixOptions {
+                threads: args.threads,
+            },
+        )
+    })?;
+    let tree = ncdprime_core::build_tree(&d, args.method)?;
+
+    let labels: Vec<&str> = set.items.iter().map(|i| i.label.as_str()).collect();
+    println!("{}", tree.to_newick(&labels));
+    Ok(())
+}
@@ -0,0 +1,70 @@
+//! Per-call encoder setup versus a reused `Compressor::context`, on the small
+//! inputs (log lines, identifiers) where setup dominates.
+//!
+//! Run with `cargo bench -p ncdprime-core --bench contexts`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use ncdprime_core::CompressorSpec;
+
+fn lines(n: usize) -> Vec<Vec<u8>> {
+    (0..n)
+        .map(|i| {
+            format!(
+                "2024-05-{:02} host{} sshd: accepted key for user{} port {}",
+                i % 28 + 1,
+                i % 5,
+                i % 11,
+                40_000 + i * 37 % 999
+            )
+            .into_bytes()
+        })
+        .collect()
+}
+
+fn time(mut f: impl FnMut()) -> Duration {
+    f();
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+fn main() {
+    let items = lines(2000);
+    let calls = items.len() as f64;
+
+    println!(
+        "{:<14} {:>12} {:>12} {:>8}",
+        "compressor", "fresh ns", "context ns", "speedup"
+    );
+    for spec in [
+        "gzip",
+        "gzip:level=6",
+        "zstd",
+        "zstd-raw",
+        "lz4",
+        "lz4:hc=9",
+    ] {
+        let c = spec.parse::<CompressorSpec>().unwrap().build();
+
+        let fresh = time(|| {
+            for x in &items {
+                black_box(c.compressed_len(x).unwrap());
+            }
+        });
+        let reused = time(|| {
+            let mut ctx = c.context();
+            for x in &items {
+                black_box(ctx.compressed_len(x).unwrap());
+            }
+        });
+
+        let fresh_ns = fresh.as_nanos() as f64 / calls;
+        let reused_ns = reused.as_nanos() as f64 / calls;
+        println!(
+            "{spec:<14} {fresh_ns:>12.0} {reused_ns:>12.0} {:>7.1}x",
+            fresh_ns / reused_ns
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! C(x || y) along a matrix row: compressing the whole join for every column
+//! versus resuming from the encoder state after x
+//! (`CompressorContext::compressed_len_resumed`).
+//!
+//! Run with `cargo bench -p ncdprime-core --bench prefix`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use ncdprime_core::CompressorSpec;
+
+/// Loosely related text records of about `len` bytes.
+fn record(seed: usize, len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len + 64);
+    let mut i = seed;
+    while out.len() < len {
+        out.extend_from_slice(
+            format!(
+                "id={} kind={} value={};",
+                i % 97,
+                (i * 7) % 13,
+                (i * 31) % 1009
+            )
+            .as_bytes(),
+        );
+        i += seed % 5 + 1;
+    }
+    out.truncate(len);
+    out
+}
+
+fn time(mut f: impl FnMut()) -> Duration {
+    f();
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+fn main() {
+    let cols = 64;
+    println!(
+        "{:<10} {:>8} {:>12} {:>12} {:>8}",
+        "compressor", "bytes", "full us", "resumed us", "speedup"
+    );
+    for spec in ["gzip", "gzip:level=6"] {
+        let c = spec.parse::<CompressorSpec>().unwrap().build();
+        for len in [256, 1024, 4096, 16 * 1024, 64 * 1024] {
+            let x = record(1, len);
+            let ys: Vec<Vec<u8>> = (2..cols + 2).map(|s| record(s, len)).collect();
+
+            let mut ctx = c.context();
+            let full = time(|| {
+                for y in &ys {
+                    black_box(ctx.compressed_len_parts(&[&x, y]).unwrap());
+                }
+            });
+            let mut ctx = c.context();
+            let resumed = time(|| {
+                for y in &ys {
+                    black_box(ctx.compressed_len_resumed(&[&x], &[y]).unwrap());
+                }
+            });
+
+            let per = |d: Duration| d.as_secs_f64() * 1e6 / cols as f64;
+            println!(
+                "{spec:<10} {len:>8} {:>12.1} {:>12.1} {:>7.2}x",
+                per(full),
+                per(resumed),
+                full.as_secs_f64() / resumed.as_secs_f64()
+            );
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use crate::sequence::pair_distances;
+use crate::{Compressor, MatrixOptions, NcdError, NcdMatrixProgress, NcdOptions, NcdResult};
+
+named_enum! {
+    /// How far an item's baseline distance is from the recent ones, in units
+    /// of their spread.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+    pub enum Threshold("threshold") {
+        /// (d - mean) / standard deviation.
+        #[default]
+        ZScore => "zscore",
+        /// (d - median) / (1.4826 * median absolute deviation). The scale
+        /// factor makes it comparable to a z-score; one earlier outlier barely
+        /// moves it.
+        Mad => "mad",
+    }
+}
+
+impl Threshold {
+    /// Score `d` against `history`, flooring the spread at `MIN_SPREAD`.
+    fn score(&self, d: f64, history: &[f64]) -> f64 {
+        let (center, spread) = match self {
+            Threshold::ZScore => {
+                let n = history.len() as f64;
+                let mean = history.iter().sum::<f64>() / n;
+                let var = history.iter().map(|h| (h - mean).powi(2)).sum::<f64>() / n;
+                (mean, var.sqrt())
+            }
+            Threshold::Mad => {
+                let mid = median(history.to_vec());
+                let mad = median(history.iter().map(|h| (h - mid).abs()).collect());
+                (mid, 1.4826 * mad)
+            }
+        };
+        (d - center) / spread.max(MIN_SPREAD)
+    }
+}
+
+/// Smallest spread a score divides by, so a perfectly steady history doesn't
+/// turn the next tiny wobble into an infinite score.
+const MIN_SPREAD: f64 = 1e-6;
+
+fn median(mut v: Vec<f64>) -> f64 {
+    v.sort_by(f64::total_cmp);
+    let mid = v.len() / 2;
+    if v.len().is_multiple_of(2) {
+        (v[mid - 1] + v[mid]) / 2.0
+    } else {
+        v[mid]
+    }
+}
+
+/// Anomaly detection settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnomalyOptions {
+    /// How many preceding items form an item's baseline, and how many
+    /// preceding baseline distances its score is measured against.
+    pub baseline: usize,
+    pub threshold: Threshold,
+    /// Items scoring above this are flagged.
+    pub cutoff: f64,
+}
+
+impl Default for AnomalyOptions {
+    fn default() -> Self {
+        Self {
+            baseline: 8,
+            threshold: Threshold::default(),
+            cutoff: 3.0,
+        }
+    }
+}
+
+/// One item's result from `ncd_anomalies`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnomalyScore {
+    /// Index into the items.
+    pub index: usize,
+    /// Mean NCD between the item and each item of its baseline.
+    pub distance: f64,
+    /// `distance` scored against the preceding items' distances; 0 until
+    /// there are at least two of them.
+    pub score: f64,
+    pub anomalous: bool,
+}
+
+/// Flag items that are unusually far from the items just before them.
+///
+/// Item `i`'s baseline is the `baseline` items before it; its distance is the
+/// mean NCD to them. That distance is scored (see `Threshold`) against the
+/// distances of the preceding `baseline` items, and flagged when the score
+/// exceeds `cutoff`. Only increases count: an item that fits in better than
+/// usual is not an anomaly. Each C(x) is compressed once however many
+/// baselines the item is part of. Item 0 has no baseline, so results start at
+/// item 1.
+pub fn ncd_anomalies<C: Compressor + ?Sized, S: AsRef<[u8]>>(
+    c: &C,
+    items: &[S],
+    opts: NcdOptions,
+    aopts: &AnomalyOptions,
+    mopts: &MatrixOptions,
+) -> NcdResult<Vec<AnomalyScore>> {
+    ncd_anomalies_with_progress(c, items, opts, aopts, mopts, |_| {})
+}
+
+/// `ncd_anomalies` with a per-cell progress callback; the cell comparing
+/// item `i` with baseline item `j` is reported as row `i`, column `j`.
+///
+/// Item `i` is x in each of its C(xy), and all of an item's cells run on one
+/// worker, so compressors that resume from a shared prefix (gzip) reuse x's
+/// encoder state across its baseline.
+pub fn ncd_anomalies_with_progress<C: Compressor + ?Sized, S: AsRef<[u8]>, F>(
+    c: &C,
+    items: &[S],
+    opts: NcdOptions,
+    aopts: &AnomalyOptions,
+    mopts: &MatrixOptions,
+    on_cell: F,
+) -> NcdResult<Vec<AnomalyScore>>
+where
+    F: FnMut(NcdMatrixProgress),
+{
+    if aopts.baseline == 0 {
+        return Err(NcdError::invalid(
+            "anomaly baseline must be at least 1 item",
+        ));
+    }
+    let pairs: Vec<(usize, usize)> = (1..items.len())
+        .flat_map(|i| (i.saturating_sub(aopts.baseline)..i).map(move |j| (i, j)))
+        .collect();
+    let cells = pair_distances(c, items, &pairs, opts, mopts, on_cell)?;
+
+    // Pairs are grouped by item, in item order.
+    let mut distances = Vec::with_capacity(items.len().saturating_sub(1));
+    let mut rest = &cells[..];
+    for i in 1..items.len() {
+        let (mine, tail) = rest.split_at(i.min(aopts.baseline));
+        distances.push(mine.iter().sum::<f64>() / mine.len() as f64);
+        rest = tail;
+    }
+
+    Ok(distances
+        .iter()
+        .enumerate()
+        .map(|(k, &distance)| {
+            let history = &distances[k.saturating_sub(aopts.baseline)..k];
+            let score = if history.len() < 2 {
+                0.0
+            } else {
+                aopts.threshold.score(distance, history)
+            };
+            AnomalyScore {
+                index: k + 1,
+                distance,
+                score,
+                anomalous: score > aopts.cutoff,
+            }
+        })
+        .collect())
+}
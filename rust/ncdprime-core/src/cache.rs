@@ -0,0 +1,311 @@
+use crate::{Compressor, CompressorContext, CompressorSpec};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// File header: format name + version.
+const MAGIC: &[u8; 8] = b"NCDPSC01";
+/// One record: 32-byte key || u64_le(size).
+const RECORD_LEN: usize = 40;
+
+/// Counters describing a `SizeCache`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CacheStats {
+    pub entries: usize,
+    /// Size of the backing file on disk (0 if it does not exist yet).
+    pub file_bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<[u8; 32], u64>,
+    /// Keys in insertion order (oldest first), used for eviction.
+    order: Vec<[u8; 32]>,
+    /// Number of trailing `order` entries not yet written to disk.
+    unsaved: usize,
+    hits: u64,
+    misses: u64,
+}
+
+/// A persistent, file-backed store of compressed sizes.
+///
+/// Keys are blake3 hashes over the compressor spec (id + all parameters) and the
+/// exact bytes that were compressed. Singletons C(x) and joined pairs C(xy) are
+/// both stored this way; since the pair key covers the joined buffer, the join
+/// mode is part of the key too.
+///
+/// The file is an append-only log of fixed-size records, so saving only writes
+/// new entries unless `max_entries` forces a compaction.
+pub struct SizeCache {
+    path: PathBuf,
+    max_entries: Option<usize>,
+    state: Mutex<CacheState>,
+}
+
+impl SizeCache {
+    /// Open (or lazily create) a cache file.
+    pub fn open(path: impl Into<PathBuf>, max_entries: Option<usize>) -> io::Result<Self> {
+        let path = path.into();
+        let mut state = CacheState::default();
+
+        match fs::File::open(&path) {
+            Ok(mut f) => {
+                let mut buf = Vec::new();
+                f.read_to_end(&mut buf)?;
+                if !buf.is_empty() && !buf.starts_with(MAGIC) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("not an ncdprime size cache: {}", path.display()),
+                    ));
+                }
+                // A torn trailing record (e.g. from a crash mid-append) is ignored.
+                for rec in buf
+                    .get(MAGIC.len()..)
+                    .unwrap_or(&[])
+                    .chunks_exact(RECORD_LEN)
+                {
+                    let key: [u8; 32] = rec[..32].try_into().expect("record key");
+                    let size = u64::from_le_bytes(rec[32..].try_into().expect("record size"));
+                    if state.entries.insert(key, size).is_none() {
+                        state.order.push(key);
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(Self {
+            path,
+            max_entries,
+            state: Mutex::new(state),
+        })
+    }
+
+    /// The default cache location.
+    ///
+    /// `$NCDPRIME_CACHE`, else `$XDG_CACHE_HOME/ncdprime/sizes.bin`, else
+    /// `$HOME/.cache/ncdprime/sizes.bin`.
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(p) = std::env::var_os("NCDPRIME_CACHE") {
+            return Some(PathBuf::from(p));
+        }
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| Path::new(&h).join(".cache")))?;
+        Some(base.join("ncdprime").join("sizes.bin"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Cache key for compressing `input` with `spec`.
+    pub fn key(spec: &CompressorSpec, input: &[u8]) -> [u8; 32] {
+        Self::key_parts(spec, &[input])
+    }
+
+    /// The key of the concatenation of `parts`; equal to `key` on the joined buffer.
+    pub fn key_parts(spec: &CompressorSpec, parts: &[&[u8]]) -> [u8; 32] {
+        let mut h = blake3::Hasher::new();
+        h.update(b"ncdprime-size-v1\0");
+        h.update(spec.cache_key().as_bytes());
+        h.update(b"\0");
+        for p in parts {
+            h.update(p);
+        }
+        *h.finalize().as_bytes()
+    }
+
+    pub fn get(&self, key: &[u8; 32]) -> Option<u64> {
+        let mut st = self.lock();
+        let v = st.entries.get(key).copied();
+        match v {
+            Some(_) => st.hits += 1,
+            None => st.misses += 1,
+        }
+        v
+    }
+
+    pub fn insert(&self, key: [u8; 32], size: u64) {
+        let mut st = self.lock();
+        if st.entries.insert(key, size).is_none() {
+            st.order.push(key);
+            st.unsaved += 1;
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let st = self.lock();
+        CacheStats {
+            entries: st.entries.len(),
+            file_bytes: fs::metadata(&self.path).map_or(0, |m| m.len()),
+            hits: st.hits,
+            misses: st.misses,
+        }
+    }
+
+    /// Persist new entries, evicting the oldest ones beyond `max_entries`.
+    pub fn save(&self) -> io::Result<()> {
+        let mut st = self.lock();
+
+        let over = self
+            .max_entries
+            .map_or(0, |max| st.order.len().saturating_sub(max));
+        if over > 0 {
+            let evicted: Vec<[u8; 32]> = st.order.drain(..over).collect();
+            for k in &evicted {
+                st.entries.remove(k);
+            }
+            st.unsaved = st.order.len();
+            return self.rewrite(&mut st);
+        }
+
+        if st.unsaved == 0 {
+            return Ok(());
+        }
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Missing header or a torn trailing record: appending would misalign.
+        let existing = fs::metadata(&self.path).map_or(0, |m| m.len());
+        let header = MAGIC.len() as u64;
+        if existing < header || !(existing - header).is_multiple_of(RECORD_LEN as u64) {
+            return self.rewrite(&mut st);
+        }
+
+        let mut buf = Vec::with_capacity(st.unsaved * RECORD_LEN);
+        for k in &st.order[st.order.len() - st.unsaved..] {
+            push_record(&mut buf, k, st.entries[k]);
+        }
+        let mut f = fs::OpenOptions::new().append(true).open(&self.path)?;
+        f.write_all(&buf)?;
+        st.unsaved = 0;
+        Ok(())
+    }
+
+    /// Drop every entry and delete the backing file.
+    pub fn clear(&self) -> io::Result<()> {
+        *self.lock() = CacheState::default();
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn rewrite(&self, st: &mut CacheState) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut buf = Vec::with_capacity(MAGIC.len() + st.order.len() * RECORD_LEN);
+        buf.extend_from_slice(MAGIC);
+        for k in &st.order {
+            push_record(&mut buf, k, st.entries[k]);
+        }
+
+        // Write-then-rename so a crash never leaves a half-written cache.
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, &buf)?;
+        fs::rename(&tmp, &self.path)?;
+        st.unsaved = 0;
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn push_record(buf: &mut Vec<u8>, key: &[u8; 32], size: u64) {
+    buf.extend_from_slice(key);
+    buf.extend_from_slice(&size.to_le_bytes());
+}
+
+/// A compressor that consults a `SizeCache` before compressing.
+///
+/// Wrapping the compressor (rather than the matrix loop) means singleton and
+/// pair sizes are cached alike, and any NCD entry point can use it.
+pub struct CachedCompressor<'a, C: Compressor + ?Sized> {
+    inner: &'a C,
+    spec: CompressorSpec,
+    cache: &'a SizeCache,
+}
+
+impl<'a, C: Compressor + ?Sized> CachedCompressor<'a, C> {
+    /// `spec` must describe `inner`; it is what keys the cache entries.
+    pub fn new(inner: &'a C, spec: &CompressorSpec, cache: &'a SizeCache) -> Self {
+        Self {
+            inner,
+            spec: spec.clone(),
+            cache,
+        }
+    }
+}
+
+impl<C: Compressor + ?Sized> Compressor for CachedCompressor<'_, C> {
+    fn id(&self) -> &'static str {
+        self.inner.id()
+    }
+
+    fn compressed_len(&self, input: &[u8]) -> io::Result<usize> {
+        let key = SizeCache::key(&self.spec, input);
+        if let Some(n) = self.cache.get(&key) {
+            return Ok(n as usize);
+        }
+        let n = self.inner.compressed_len(input)?;
+        self.cache.insert(key, n as u64);
+        Ok(n)
+    }
+
+    fn compressed_len_parts(&self, parts: &[&[u8]]) -> io::Result<usize> {
+        let key = SizeCache::key_parts(&self.spec, parts);
+        if let Some(n) = self.cache.get(&key) {
+            return Ok(n as usize);
+        }
+        let n = self.inner.compressed_len_parts(parts)?;
+        self.cache.insert(key, n as u64);
+        Ok(n)
+    }
+
+    fn context(&self) -> Box<dyn CompressorContext + '_> {
+        Box::new(CachedContext {
+            inner: self.inner.context(),
+            spec: &self.spec,
+            cache: self.cache,
+        })
+    }
+}
+
+/// Cache lookups in front of the wrapped compressor's own context.
+struct CachedContext<'a> {
+    inner: Box<dyn CompressorContext + 'a>,
+    spec: &'a CompressorSpec,
+    cache: &'a SizeCache,
+}
+
+impl CompressorContext for CachedContext<'_> {
+    fn compressed_len_parts(&mut self, parts: &[&[u8]]) -> io::Result<usize> {
+        let key = SizeCache::key_parts(self.spec, parts);
+        if let Some(n) = self.cache.get(&key) {
+            return Ok(n as usize);
+        }
+        let n = self.inner.compressed_len_parts(parts)?;
+        self.cache.insert(key, n as u64);
+        Ok(n)
+    }
+
+    fn compressed_len_resumed(&mut self, prefix: &[&[u8]], suffix: &[&[u8]]) -> io::Result<usize> {
+        let key = SizeCache::key_parts(self.spec, &[prefix, suffix].concat());
+        if let Some(n) = self.cache.get(&key) {
+            return Ok(n as usize);
+        }
+        let n = self.inner.compressed_len_resumed(prefix, suffix)?;
+        self.cache.insert(key, n as u64);
+        Ok(n)
+    }
+}
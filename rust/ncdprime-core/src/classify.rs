@@ -0,0 +1,173 @@
+use crate::{Compressor, MatrixOptions, NcdError, NcdOptions, NcdResult, Neighbor, ncd_nearest};
+
+named_enum! {
+    /// How the k nearest training items vote for a class.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+    pub enum Vote("vote") {
+        /// One vote per neighbor.
+        #[default]
+        Majority => "majority",
+        /// Each neighbor votes with weight 1 / distance, so near items count
+        /// more.
+        Distance => "distance",
+    }
+}
+
+named_enum! {
+    /// Which class wins when several get the same vote.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+    pub enum TieBreak("tie break") {
+        /// The tied class with the nearest neighbor.
+        #[default]
+        Nearest => "nearest",
+        /// Drop the farthest neighbor and vote again, down to the single
+        /// nearest.
+        Shrink => "shrink",
+        /// The tied class with the lowest index.
+        First => "first",
+    }
+}
+
+/// k-NN classifier settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KnnOptions {
+    pub k: usize,
+    pub vote: Vote,
+    pub tie_break: TieBreak,
+}
+
+impl Default for KnnOptions {
+    fn default() -> Self {
+        Self {
+            k: 3,
+            vote: Vote::default(),
+            tie_break: TieBreak::default(),
+        }
+    }
+}
+
+/// The class `neighbors` (nearest first, as from `ncd_nearest`) vote for,
+/// where training item `i` has class `labels[i]`.
+///
+/// `None` if nobody votes: `neighbors` is empty or `opts.k` is 0.
+pub fn knn_vote(neighbors: &[Neighbor], labels: &[usize], opts: &KnnOptions) -> Option<usize> {
+    let mut neighbors = &neighbors[..neighbors.len().min(opts.k)];
+    loop {
+        // (class, score, rank of its nearest neighbor), in order of first appearance.
+        let mut scores: Vec<(usize, f64, usize)> = Vec::new();
+        for (rank, n) in neighbors.iter().enumerate() {
+            let weight = match opts.vote {
+                Vote::Majority => 1.0,
+                Vote::Distance => 1.0 / n.distance.max(f64::MIN_POSITIVE),
+            };
+            let class = labels[n.index];
+            match scores.iter_mut().find(|s| s.0 == class) {
+                Some(s) => s.1 += weight,
+                None => scores.push((class, weight, rank)),
+            }
+        }
+        let best = scores.iter().map(|s| s.1).fold(f64::NEG_INFINITY, f64::max);
+        let tied: Vec<&(usize, f64, usize)> = scores.iter().filter(|s| s.1 == best).collect();
+
+        match (tied.as_slice(), opts.tie_break) {
+            ([only], _) => return Some(only.0),
+            (_, TieBreak::Shrink) if neighbors.len() > 1 => {
+                neighbors = &neighbors[..neighbors.len() - 1];
+            }
+            (_, TieBreak::First) => return tied.iter().map(|s| s.0).min(),
+            _ => return tied.iter().min_by_key(|s| s.2).map(|s| s.0),
+        }
+    }
+}
+
+/// Classify each `test` item by its `opts.k` nearest `train` items, where
+/// training item `i` has class `labels[i]`.
+pub fn ncd_classify<C: Compressor + ?Sized>(
+    c: &C,
+    train: &[Vec<u8>],
+    labels: &[usize],
+    test: &[Vec<u8>],
+    opts: NcdOptions,
+    kopts: &KnnOptions,
+    mopts: &MatrixOptions,
+) -> NcdResult<Vec<usize>> {
+    if train.is_empty() {
+        return Err(NcdError::invalid("cannot classify without training items"));
+    }
+    if labels.len() != train.len() {
+        return Err(NcdError::invalid(format!(
+            "{} labels for {} training items",
+            labels.len(),
+            train.len()
+        )));
+    }
+    let nearest = ncd_nearest(c, test, train, kopts.k, opts, mopts)?;
+    Ok(nearest
+        .iter()
+        .map(|neighbors| {
+            knn_vote(neighbors, labels, kopts)
+                .expect("ncd_nearest checked k >= 1 and train is not empty")
+        })
+        .collect())
+}
+
+/// Counts of (actual, predicted) class pairs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Confusion {
+    /// `counts[actual][predicted]`.
+    pub counts: Vec<Vec<usize>>,
+}
+
+impl Confusion {
+    pub fn new(classes: usize) -> Self {
+        Self {
+            counts: vec![vec![0; classes]; classes],
+        }
+    }
+
+    /// Tally predictions against the true classes.
+    pub fn from_predictions(classes: usize, actual: &[usize], predicted: &[usize]) -> Self {
+        let mut m = Self::new(classes);
+        for (&a, &p) in actual.iter().zip(predicted) {
+            m.add(a, p);
+        }
+        m
+    }
+
+    pub fn add(&mut self, actual: usize, predicted: usize) {
+        self.counts[actual][predicted] += 1;
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().flatten().sum()
+    }
+
+    /// How many items of class `class` there are.
+    pub fn support(&self, class: usize) -> usize {
+        self.counts[class].iter().sum()
+    }
+
+    pub fn correct(&self) -> usize {
+        (0..self.counts.len()).map(|c| self.counts[c][c]).sum()
+    }
+
+    /// Fraction of items classified correctly (0 when there are none).
+    pub fn accuracy(&self) -> f64 {
+        ratio(self.correct(), self.total())
+    }
+
+    /// Fraction of the items predicted as `class` that are (0 when none are).
+    pub fn precision(&self, class: usize) -> f64 {
+        let predicted: usize = self.counts.iter().map(|row| row[class]).sum();
+        ratio(self.counts[class][class], predicted)
+    }
+
+    /// Fraction of the items of `class` predicted as it (0 when there are none).
+    pub fn recall(&self, class: usize) -> f64 {
+        ratio(self.counts[class][class], self.support(class))
+    }
+}
+
+fn ratio(n: usize, d: usize) -> f64 {
+    if d == 0 { 0.0 } else { n as f64 / d as f64 }
+}
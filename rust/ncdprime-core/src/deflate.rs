@@ -0,0 +1,179 @@
+use crate::CompressorContext;
+use miniz_oxide::DataFormat;
+use miniz_oxide::deflate::core::{CompressorOxide, TDEFLFlush, TDEFLStatus, compress};
+use std::io;
+
+/// Output space flate2's `GzEncoder` hands the encoder at a time. Where its
+/// output lands decides where blocks end, so sizes only match flate2's when
+/// each window is filled across `compress` calls and input parts, and a new
+/// one starts only once it is full or the stream is finished.
+const WINDOW: usize = 32 * 1024;
+
+/// Prefixes shorter than this are compressed again rather than resumed: copying
+/// the encoder state costs as much as compressing about 1 KiB (see the `prefix`
+/// benchmark).
+const RESUME_MIN_PREFIX: usize = 2 * 1024;
+
+/// A raw deflate encoder that is reset, not rebuilt, between inputs. It sizes
+/// gzip too, since the gzip header and trailer have a fixed length.
+///
+/// This is miniz_oxide, the engine behind flate2's default backend, driven
+/// directly: its state can be cloned, so C(x || y) for many y can resume from
+/// a snapshot taken after x instead of compressing x every time.
+pub(crate) struct DeflateContext {
+    state: Box<CompressorOxide>,
+    /// Added to every size (the gzip framing, or 0).
+    overhead: usize,
+    /// The current output window, and how much of it is used.
+    scratch: Vec<u8>,
+    used: usize,
+    prefix: Option<Snapshot>,
+    /// Hash of the last prefix that did not match `prefix`. A snapshot costs a
+    /// state clone and a copy of the prefix, so it is only taken once the same
+    /// prefix comes back, as along a matrix row; callers whose prefix changes
+    /// on every call never pay for one.
+    missed: Option<blake3::Hash>,
+}
+
+/// The encoder right after compressing `bytes`, and what it had written.
+struct Snapshot {
+    bytes: Vec<u8>,
+    state: Box<CompressorOxide>,
+    written: usize,
+    used: usize,
+}
+
+impl Snapshot {
+    fn matches(&self, prefix: &[&[u8]]) -> bool {
+        let mut rest = &self.bytes[..];
+        for part in prefix {
+            match rest.strip_prefix(*part) {
+                Some(r) => rest = r,
+                None => return false,
+            }
+        }
+        rest.is_empty()
+    }
+}
+
+impl DeflateContext {
+    pub(crate) fn new(level: u8, overhead: usize) -> Self {
+        let mut state = Box::<CompressorOxide>::default();
+        state.set_format_and_level(DataFormat::Raw, level);
+        Self {
+            state,
+            overhead,
+            scratch: vec![0; WINDOW],
+            used: 0,
+            prefix: None,
+            missed: None,
+        }
+    }
+
+    /// Feed `parts` to the encoder, returning the bytes it wrote meanwhile.
+    fn feed(&mut self, parts: &[&[u8]]) -> io::Result<usize> {
+        let mut written = 0;
+        for part in parts {
+            let mut input = *part;
+            while !input.is_empty() {
+                if self.used == WINDOW {
+                    self.used = 0;
+                }
+                let (status, read, out) = compress(
+                    &mut self.state,
+                    input,
+                    &mut self.scratch[self.used..],
+                    TDEFLFlush::None,
+                );
+                check(status)?;
+                if read == 0 && out == 0 {
+                    return Err(io::Error::other("deflate made no progress"));
+                }
+                input = &input[read..];
+                self.used += out;
+                written += out;
+            }
+        }
+        Ok(written)
+    }
+
+    /// End the stream, returning the bytes written by doing so.
+    fn finish(&mut self) -> io::Result<usize> {
+        let mut written = 0;
+        self.used = 0;
+        loop {
+            if self.used == WINDOW {
+                self.used = 0;
+            }
+            let (status, _, out) = compress(
+                &mut self.state,
+                &[],
+                &mut self.scratch[self.used..],
+                TDEFLFlush::Finish,
+            );
+            self.used += out;
+            written += out;
+            if check(status)? == TDEFLStatus::Done {
+                return Ok(written);
+            }
+        }
+    }
+}
+
+fn check(status: TDEFLStatus) -> io::Result<TDEFLStatus> {
+    match status {
+        TDEFLStatus::Okay | TDEFLStatus::Done => Ok(status),
+        _ => Err(io::Error::other(format!("deflate failed: {status:?}"))),
+    }
+}
+
+impl CompressorContext for DeflateContext {
+    fn compressed_len_parts(&mut self, parts: &[&[u8]]) -> io::Result<usize> {
+        self.state.reset();
+        self.used = 0;
+        let n = self.feed(parts)? + self.finish()?;
+        Ok(n + self.overhead)
+    }
+
+    fn compressed_len_resumed(&mut self, prefix: &[&[u8]], suffix: &[&[u8]]) -> io::Result<usize> {
+        let prefix_len: usize = prefix.iter().map(|p| p.len()).sum();
+        if prefix_len < RESUME_MIN_PREFIX {
+            return self.compressed_len_parts(&[prefix, suffix].concat());
+        }
+
+        let written = match &self.prefix {
+            Some(snap) if snap.matches(prefix) => {
+                self.state.clone_from(&snap.state);
+                self.used = snap.used;
+                snap.written
+            }
+            _ => {
+                let hash = prefix_hash(prefix);
+                if self.missed.replace(hash) != Some(hash) {
+                    return self.compressed_len_parts(&[prefix, suffix].concat());
+                }
+                self.missed = None;
+                self.state.reset();
+                self.used = 0;
+                let written = self.feed(prefix)?;
+                self.prefix = Some(Snapshot {
+                    bytes: prefix.concat(),
+                    state: self.state.clone(),
+                    written,
+                    used: self.used,
+                });
+                written
+            }
+        };
+        let n = written + self.feed(suffix)? + self.finish()?;
+        Ok(n + self.overhead)
+    }
+}
+
+fn prefix_hash(prefix: &[&[u8]]) -> blake3::Hash {
+    let mut h = blake3::Hasher::new();
+    for part in prefix {
+        h.update(part);
+    }
+    h.finalize()
+}
@@ -0,0 +1,119 @@
+use std::{error, fmt, io};
+
+/// Errors returned by the spec parsers and the NCD entry points.
+///
+/// `Compressor` implementations keep returning `io::Result`; an `NcdError`
+/// converted into an `io::Error` (e.g. `InputTooLarge` from a compressor)
+/// converts back into the same variant rather than becoming `Compressor`.
+#[derive(Debug)]
+pub enum NcdError {
+    /// A compressor spec or option that does not parse: unknown id, parameter
+    /// or option name, malformed value.
+    InvalidSpec(String),
+    /// A numeric parameter outside its accepted range.
+    OutOfRange {
+        /// What the value is for, e.g. `zstd level` or `join interleave chunk`.
+        name: String,
+        value: i64,
+        min: i64,
+        max: i64,
+    },
+    /// The compressor `id` failed while compressing.
+    Compressor { id: &'static str, source: io::Error },
+    /// An input (or joined pair) larger than the compressor accepts.
+    InputTooLarge {
+        id: &'static str,
+        len: usize,
+        max: usize,
+    },
+    /// A callback asked to stop the computation.
+    Cancelled,
+    /// Reading or writing a file (dictionary, cache, output sink) failed.
+    Io(io::Error),
+}
+
+/// `Result` with `NcdError` as the error type.
+pub type NcdResult<T> = Result<T, NcdError>;
+
+impl NcdError {
+    pub(crate) fn invalid(msg: impl Into<String>) -> Self {
+        NcdError::InvalidSpec(msg.into())
+    }
+
+    /// Attribute a failure of compressor `id` to it, unless it already
+    /// carries a more specific `NcdError`.
+    pub(crate) fn compressor(id: &'static str, e: io::Error) -> Self {
+        match NcdError::from(e) {
+            NcdError::Io(source) => NcdError::Compressor { id, source },
+            e => e,
+        }
+    }
+
+    fn io_kind(&self) -> io::ErrorKind {
+        match self {
+            NcdError::InvalidSpec(_)
+            | NcdError::OutOfRange { .. }
+            | NcdError::InputTooLarge { .. } => io::ErrorKind::InvalidInput,
+            NcdError::Compressor { source: e, .. } | NcdError::Io(e) => e.kind(),
+            NcdError::Cancelled => io::ErrorKind::Interrupted,
+        }
+    }
+
+    /// Whether the caller's request was at fault (a bad spec, option or
+    /// input) rather than the compressor or the environment.
+    pub fn is_invalid_input(&self) -> bool {
+        matches!(
+            self,
+            NcdError::InvalidSpec(_) | NcdError::OutOfRange { .. } | NcdError::InputTooLarge { .. }
+        )
+    }
+}
+
+impl fmt::Display for NcdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NcdError::InvalidSpec(msg) => f.write_str(msg),
+            NcdError::OutOfRange {
+                name,
+                value,
+                min,
+                max,
+            } => write!(f, "{name} out of range: {value} (expected {min}..={max})"),
+            NcdError::Compressor { id, source } => write!(f, "{id}: {source}"),
+            NcdError::InputTooLarge { id, len, max } => {
+                write!(f, "{id}: input of {len} bytes exceeds the {max}-byte limit")
+            }
+            NcdError::Cancelled => f.write_str("cancelled"),
+            NcdError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl error::Error for NcdError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            NcdError::Compressor { source, .. } => Some(source),
+            NcdError::Io(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for NcdError {
+    fn from(e: io::Error) -> Self {
+        if e.get_ref().is_some_and(|inner| inner.is::<NcdError>()) {
+            let inner = e.into_inner().expect("checked above");
+            return *inner.downcast::<NcdError>().expect("checked above");
+        }
+        NcdError::Io(e)
+    }
+}
+
+impl From<NcdError> for io::Error {
+    fn from(e: NcdError) -> Self {
+        match e {
+            NcdError::Io(e) => e,
+            e => io::Error::new(e.io_kind(), e),
+        }
+    }
+}
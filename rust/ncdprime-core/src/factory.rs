@@ -1,13 +1,57 @@
-use crate::{Brotli, Compressor, Gzip, Lz4, Xz, Zstd};
-use std::io;
+use crate::{
+    Brotli, Bzip2, Compressor, Gzip, Lz4, NcdError, NcdResult, Ppmd, Xz, Zstd, ZstdDict,
+    ZstdDictionary,
+};
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum CompressorSpec {
-    Gzip { level: u32 },
-    Zstd { level: i32 },
-    Brotli { quality: u32, lgwin: u32 },
-    Lz4 { accel: i32 },
-    Xz { level: u32 },
+    Gzip {
+        level: u32,
+    },
+    Zstd {
+        level: i32,
+    },
+    Brotli {
+        quality: u32,
+        lgwin: u32,
+    },
+    Lz4 {
+        accel: i32,
+        /// LZ4-HC level; 0 = fast mode (which uses `accel`).
+        hc: u32,
+    },
+    Xz {
+        level: u32,
+    },
+    Bzip2 {
+        level: u32,
+    },
+    Ppmd {
+        order: u32,
+        /// Model memory in MiB.
+        mem_mb: u32,
+    },
+    /// zstd with a preset dictionary, e.g. from `train_zstd_dictionary`.
+    ZstdDict {
+        level: i32,
+        dict: ZstdDictionary,
+    },
+    /// Header-free variants: sizes exclude container framing (see `Gzip::raw` etc.).
+    GzipRaw {
+        level: u32,
+    },
+    ZstdRaw {
+        level: i32,
+    },
+    Lz4Raw {
+        accel: i32,
+        hc: u32,
+    },
+    XzRaw {
+        level: u32,
+    },
 }
 
 impl Default for CompressorSpec {
@@ -16,7 +60,175 @@ impl Default for CompressorSpec {
     }
 }
 
+/// A tunable compressor parameter, as named by every frontend.
+#[derive(Clone, Copy, Debug)]
+pub struct ParamInfo {
+    pub name: &'static str,
+    /// Short spelling accepted in spec strings (`brotli:q=11`).
+    pub alias: Option<&'static str>,
+    pub default: i64,
+    /// Inclusive range of accepted values.
+    pub min: i64,
+    pub max: i64,
+}
+
+impl ParamInfo {
+    fn is_named(&self, name: &str) -> bool {
+        self.name == name || self.alias == Some(name)
+    }
+}
+
+const fn param(name: &'static str, default: i64, min: i64, max: i64) -> ParamInfo {
+    ParamInfo {
+        name,
+        alias: None,
+        default,
+        min,
+        max,
+    }
+}
+
+const fn alias(p: ParamInfo, alias: &'static str) -> ParamInfo {
+    ParamInfo {
+        alias: Some(alias),
+        ..p
+    }
+}
+
+const GZIP_PARAMS: &[ParamInfo] = &[param("level", 9, 0, 9)];
+/// Negative zstd levels are the "fast" modes; -131072 is ZSTD_minCLevel().
+const ZSTD_PARAMS: &[ParamInfo] = &[param("level", 3, -131072, 22)];
+const BROTLI_PARAMS: &[ParamInfo] = &[
+    alias(param("quality", 11, 0, 11), "q"),
+    param("lgwin", 22, 10, 24),
+];
+/// 65537 is LZ4_ACCELERATION_MAX, 12 is LZ4HC_CLEVEL_MAX; `hc` > 0 selects LZ4-HC.
+const LZ4_PARAMS: &[ParamInfo] = &[param("accel", 1, 1, 65537), param("hc", 0, 0, 12)];
+const XZ_PARAMS: &[ParamInfo] = &[param("level", 6, 0, 9)];
+const BZIP2_PARAMS: &[ParamInfo] = &[param("level", 9, 1, 9)];
+/// 7z's PPMd defaults (order 6, 16 MiB of model memory).
+const PPMD_PARAMS: &[ParamInfo] = &[
+    alias(param("order", 6, 2, 64), "o"),
+    param("mem", 16, 1, 2048),
+];
+/// Plus the dictionary itself, which only spec strings can name (`dict=PATH`).
+const ZSTD_DICT_PARAMS: &[ParamInfo] = ZSTD_PARAMS;
+
+/// The parameters `id` accepts (with defaults), or `None` for an unknown id.
+pub fn compressor_params(id: &str) -> Option<&'static [ParamInfo]> {
+    Some(match id {
+        "gzip" => GZIP_PARAMS,
+        "zstd" => ZSTD_PARAMS,
+        "brotli" => BROTLI_PARAMS,
+        "lz4" => LZ4_PARAMS,
+        "xz" => XZ_PARAMS,
+        "bzip2" => BZIP2_PARAMS,
+        "ppmd" => PPMD_PARAMS,
+        "zstd-dict" => ZSTD_DICT_PARAMS,
+        "gzip-raw" => GZIP_PARAMS,
+        "zstd-raw" => ZSTD_PARAMS,
+        "lz4-raw" => LZ4_PARAMS,
+        "xz-raw" => XZ_PARAMS,
+        _ => return None,
+    })
+}
+
 impl CompressorSpec {
+    /// Build a spec from a compressor id and by-name parameters.
+    ///
+    /// Parameters that are not given keep the defaults from `compressor_params`;
+    /// the rest are range-checked. Names may use a parameter's alias. This is
+    /// the shared entry point for the server and language bindings.
+    ///
+    /// `zstd-dict` also needs a dictionary file, so it can only be built from a
+    /// spec string (`zstd-dict:level=3,dict=PATH`).
+    pub fn from_params<'a>(
+        id: &str,
+        params: impl IntoIterator<Item = (&'a str, i64)>,
+    ) -> NcdResult<Self> {
+        let (p, v) = resolve_params(id, params)?;
+        Ok(match id {
+            "gzip" => CompressorSpec::Gzip {
+                level: param_value(id, p[0].name, v[0])?,
+            },
+            "zstd" => CompressorSpec::Zstd {
+                level: param_value(id, p[0].name, v[0])?,
+            },
+            "brotli" => CompressorSpec::Brotli {
+                quality: param_value(id, p[0].name, v[0])?,
+                lgwin: param_value(id, p[1].name, v[1])?,
+            },
+            "lz4" => {
+                lz4_modes(id, p, &v)?;
+                CompressorSpec::Lz4 {
+                    accel: param_value(id, p[0].name, v[0])?,
+                    hc: param_value(id, p[1].name, v[1])?,
+                }
+            }
+            "xz" => CompressorSpec::Xz {
+                level: param_value(id, p[0].name, v[0])?,
+            },
+            "bzip2" => CompressorSpec::Bzip2 {
+                level: param_value(id, p[0].name, v[0])?,
+            },
+            "ppmd" => CompressorSpec::Ppmd {
+                order: param_value(id, p[0].name, v[0])?,
+                mem_mb: param_value(id, p[1].name, v[1])?,
+            },
+            "zstd-dict" => return Err(missing_dict()),
+            "gzip-raw" => CompressorSpec::GzipRaw {
+                level: param_value(id, p[0].name, v[0])?,
+            },
+            "zstd-raw" => CompressorSpec::ZstdRaw {
+                level: param_value(id, p[0].name, v[0])?,
+            },
+            "lz4-raw" => {
+                lz4_modes(id, p, &v)?;
+                CompressorSpec::Lz4Raw {
+                    accel: param_value(id, p[0].name, v[0])?,
+                    hc: param_value(id, p[1].name, v[1])?,
+                }
+            }
+            "xz-raw" => CompressorSpec::XzRaw {
+                level: param_value(id, p[0].name, v[0])?,
+            },
+            _ => unreachable!("compressor_params knows {id}"),
+        })
+    }
+
+    /// A `zstd-dict` spec over an already-loaded dictionary.
+    pub fn zstd_dict(dict: ZstdDictionary, level: i32) -> NcdResult<Self> {
+        let (p, v) = resolve_params("zstd-dict", [("level", level.into())])?;
+        Ok(CompressorSpec::ZstdDict {
+            level: param_value("zstd-dict", p[0].name, v[0])?,
+            dict,
+        })
+    }
+
+    /// The spec's parameters by name, in `compressor_params` order.
+    pub fn params(&self) -> Vec<(&'static str, i64)> {
+        match *self {
+            CompressorSpec::Gzip { level } => vec![("level", level.into())],
+            CompressorSpec::Zstd { level } => vec![("level", level.into())],
+            CompressorSpec::Brotli { quality, lgwin } => {
+                vec![("quality", quality.into()), ("lgwin", lgwin.into())]
+            }
+            CompressorSpec::Lz4 { accel, hc } => vec![("accel", accel.into()), ("hc", hc.into())],
+            CompressorSpec::Xz { level } => vec![("level", level.into())],
+            CompressorSpec::Bzip2 { level } => vec![("level", level.into())],
+            CompressorSpec::Ppmd { order, mem_mb } => {
+                vec![("order", order.into()), ("mem", mem_mb.into())]
+            }
+            CompressorSpec::ZstdDict { level, .. } => vec![("level", level.into())],
+            CompressorSpec::GzipRaw { level } => vec![("level", level.into())],
+            CompressorSpec::ZstdRaw { level } => vec![("level", level.into())],
+            CompressorSpec::Lz4Raw { accel, hc } => {
+                vec![("accel", accel.into()), ("hc", hc.into())]
+            }
+            CompressorSpec::XzRaw { level } => vec![("level", level.into())],
+        }
+    }
+
     pub fn id(&self) -> &'static str {
         match self {
             CompressorSpec::Gzip { .. } => "gzip",
@@ -24,43 +236,178 @@ impl CompressorSpec {
             CompressorSpec::Brotli { .. } => "brotli",
             CompressorSpec::Lz4 { .. } => "lz4",
             CompressorSpec::Xz { .. } => "xz",
+            CompressorSpec::Bzip2 { .. } => "bzip2",
+            CompressorSpec::Ppmd { .. } => "ppmd",
+            CompressorSpec::ZstdDict { .. } => "zstd-dict",
+            CompressorSpec::GzipRaw { .. } => "gzip-raw",
+            CompressorSpec::ZstdRaw { .. } => "zstd-raw",
+            CompressorSpec::Lz4Raw { .. } => "lz4-raw",
+            CompressorSpec::XzRaw { .. } => "xz-raw",
+        }
+    }
+
+    /// A stable string covering the compressor id and every parameter, used to
+    /// key persistent caches. This is the canonical spec string, except that a
+    /// dictionary is identified by its content hash rather than its path.
+    pub fn cache_key(&self) -> String {
+        match self {
+            CompressorSpec::ZstdDict { level, dict } => {
+                format!(
+                    "zstd-dict:level={level},dict=blake3:{}",
+                    dict.content_hash()
+                )
+            }
+            _ => self.to_string(),
         }
     }
 
     pub fn build(&self) -> Box<dyn Compressor> {
         match *self {
+            CompressorSpec::ZstdDict { level, ref dict } => Box::new(ZstdDict::new(dict, level)),
             CompressorSpec::Gzip { level } => Box::new(Gzip::new(level)),
             CompressorSpec::Zstd { level } => Box::new(Zstd::new(level)),
             CompressorSpec::Brotli { quality, lgwin } => Box::new(Brotli::new(quality, lgwin)),
-            CompressorSpec::Lz4 { accel } => Box::new(Lz4::new(accel)),
+            CompressorSpec::Lz4 { accel, hc } => Box::new(Lz4::new(accel).with_hc(hc)),
             CompressorSpec::Xz { level } => Box::new(Xz::new(level)),
+            CompressorSpec::Bzip2 { level } => Box::new(Bzip2::new(level)),
+            CompressorSpec::Ppmd { order, mem_mb } => Box::new(Ppmd::new(order, mem_mb)),
+            CompressorSpec::GzipRaw { level } => Box::new(Gzip::raw(level)),
+            CompressorSpec::ZstdRaw { level } => Box::new(Zstd::raw(level)),
+            CompressorSpec::Lz4Raw { accel, hc } => Box::new(Lz4::raw(accel).with_hc(hc)),
+            CompressorSpec::XzRaw { level } => Box::new(Xz::raw(level)),
         }
     }
 }
 
-pub fn parse_compressor(
+/// Apply by-name `params` over the defaults of `id`, checking names and ranges.
+fn resolve_params<'a>(
     id: &str,
-    gzip_level: u32,
-    zstd_level: i32,
-    brotli_quality: u32,
-    brotli_lgwin: u32,
-    lz4_accel: i32,
-    xz_level: u32,
-) -> io::Result<CompressorSpec> {
-    Ok(match id {
-        "gzip" => CompressorSpec::Gzip { level: gzip_level },
-        "zstd" => CompressorSpec::Zstd { level: zstd_level },
-        "brotli" => CompressorSpec::Brotli {
-            quality: brotli_quality,
-            lgwin: brotli_lgwin,
-        },
-        "lz4" => CompressorSpec::Lz4 { accel: lz4_accel },
-        "xz" => CompressorSpec::Xz { level: xz_level },
-        _ => {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("unknown compressor id: {id}"),
-            ));
+    params: impl IntoIterator<Item = (&'a str, i64)>,
+) -> NcdResult<(&'static [ParamInfo], Vec<i64>)> {
+    let known = compressor_params(id).ok_or_else(|| unknown_compressor(id))?;
+    let mut values: Vec<i64> = known.iter().map(|p| p.default).collect();
+    let mut seen = vec![false; known.len()];
+
+    for (name, value) in params {
+        let Some(k) = known.iter().position(|p| p.is_named(name)) else {
+            let expected: Vec<&str> = known.iter().map(|p| p.name).collect();
+            return Err(invalid(format!(
+                "unknown parameter for {id}: {name} (expected one of: {})",
+                expected.join(", ")
+            )));
+        };
+        let p = &known[k];
+        if std::mem::replace(&mut seen[k], true) {
+            return Err(invalid(format!("duplicate parameter for {id}: {}", p.name)));
         }
-    })
+        if !(p.min..=p.max).contains(&value) {
+            return Err(NcdError::OutOfRange {
+                name: format!("{id} {}", p.name),
+                value,
+                min: p.min,
+                max: p.max,
+            });
+        }
+        values[k] = value;
+    }
+    Ok((known, values))
+}
+
+/// Canonical form: the id followed by every parameter, e.g. `brotli:quality=11,lgwin=22`.
+impl fmt::Display for CompressorSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())?;
+        for (k, (name, value)) in self.params().into_iter().enumerate() {
+            let sep = if k == 0 { ':' } else { ',' };
+            write!(f, "{sep}{name}={value}")?;
+        }
+        if let CompressorSpec::ZstdDict { dict, .. } = self {
+            write!(f, ",dict={}", dict.path().display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses `id[:name=value[,name=value]...]`, e.g. `zstd:level=19` or
+/// `brotli:q=11,lgwin=24`. Omitted parameters keep their defaults.
+///
+/// `zstd-dict` takes the dictionary file as `dict=PATH` (loaded while parsing);
+/// the path may not contain a comma.
+impl FromStr for CompressorSpec {
+    type Err = NcdError;
+
+    fn from_str(s: &str) -> NcdResult<Self> {
+        let (id, rest) = match s.trim().split_once(':') {
+            Some((id, rest)) => (id, Some(rest)),
+            None => (s.trim(), None),
+        };
+
+        let mut params = Vec::new();
+        let mut dict_path = None;
+        for item in rest.map(|r| r.split(',')).into_iter().flatten() {
+            let Some((name, value)) = item.split_once('=') else {
+                return Err(invalid(format!(
+                    "invalid compressor spec `{s}`: expected name=value, got `{item}`"
+                )));
+            };
+            let (name, value) = (name.trim(), value.trim());
+            if name == "dict" {
+                if id != "zstd-dict" {
+                    return Err(invalid(format!("{id} does not take a dictionary")));
+                }
+                if dict_path.replace(value).is_some() {
+                    return Err(invalid(format!("duplicate parameter for {id}: dict")));
+                }
+                continue;
+            }
+            let value = value
+                .parse::<i64>()
+                .map_err(|_| invalid(format!("{id} {name} must be an integer, got `{value}`")))?;
+            params.push((name, value));
+        }
+
+        if id == "zstd-dict" {
+            let (p, v) = resolve_params(id, params)?;
+            let dict = ZstdDictionary::load(dict_path.ok_or_else(missing_dict)?)?;
+            return Ok(CompressorSpec::ZstdDict {
+                level: param_value(id, p[0].name, v[0])?,
+                dict,
+            });
+        }
+        CompressorSpec::from_params(id, params)
+    }
+}
+
+/// Parse a compressor spec string; see the `FromStr` impl for the grammar.
+pub fn parse_compressor(spec: &str) -> NcdResult<CompressorSpec> {
+    spec.parse()
+}
+
+fn invalid(msg: String) -> NcdError {
+    NcdError::InvalidSpec(msg)
+}
+
+fn unknown_compressor(id: &str) -> NcdError {
+    invalid(format!(
+        "unknown compressor id: {id} (expected one of: {})",
+        crate::compressor_ids().join(", ")
+    ))
+}
+
+/// Acceleration only applies to fast mode, so asking for both is a mistake.
+fn lz4_modes(id: &str, p: &[ParamInfo], v: &[i64]) -> NcdResult<()> {
+    if v[1] > 0 && v[0] != p[0].default {
+        return Err(invalid(format!(
+            "{id} accel and hc are mutually exclusive (accel applies to fast mode, hc selects LZ4-HC)"
+        )));
+    }
+    Ok(())
+}
+
+fn missing_dict() -> NcdError {
+    invalid("zstd-dict needs a dictionary: zstd-dict:dict=PATH (see `train-dict`)".to_string())
+}
+
+fn param_value<T: TryFrom<i64>>(id: &str, name: &str, value: i64) -> NcdResult<T> {
+    T::try_from(value).map_err(|_| invalid(format!("{id} {name} out of range: {value}")))
 }
@@ -1,77 +1,547 @@
+// First, so the modules below can use `named_enum!`.
+#[macro_use]
+mod named;
+
+mod anomaly;
+mod cache;
+mod classify;
+mod deflate;
+mod dict;
+mod error;
 mod factory;
+mod matrix;
+mod nearest;
+mod sequence;
+mod tree;
 
-pub use factory::{CompressorSpec, parse_compressor};
+pub use anomaly::{
+    AnomalyOptions, AnomalyScore, Threshold, ncd_anomalies, ncd_anomalies_with_progress,
+};
+pub use cache::{CacheStats, CachedCompressor, SizeCache};
+pub use classify::{Confusion, KnnOptions, TieBreak, Vote, knn_vote, ncd_classify};
+pub use dict::{DEFAULT_DICT_SIZE, ZstdDict, ZstdDictionary, train_zstd_dictionary};
+pub use error::{NcdError, NcdResult};
+pub use factory::{CompressorSpec, ParamInfo, compressor_params, parse_compressor};
+pub use matrix::{
+    MatrixOptions, MatrixRun, NcdMatrixProgress, ncd_matrix, ncd_matrix_multi,
+    ncd_matrix_multi_rows, ncd_matrix_parallel, ncd_matrix_parallel_with_progress, ncd_matrix_rows,
+    ncd_matrix_rows_resume, ncd_matrix_square, ncd_matrix_square_with_progress,
+    ncd_matrix_with_progress,
+};
+pub use nearest::{Neighbor, ncd_nearest, ncd_nearest_with_progress};
+pub use sequence::{ncd_sequence, ncd_sequence_with_progress, sliding_windows};
+pub use tree::{Tree, TreeMethod, build_tree};
 
-use std::collections::HashMap;
+use deflate::DeflateContext;
+use std::fmt;
 use std::io::{self, Read, Write};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
-#[derive(Clone, Copy, Debug)]
+/// How x and y are joined into the single input compressed for C(xy).
+///
+/// Names are `frame64`, `concat`, `sep[:HEX]` and `interleave[:BYTES]`; the
+/// parameterised modes print in full (`sep:00`, `interleave:1024`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Join {
     /// frame64(x) || frame64(y), where frame64(b) = u64_le(len) || b
     Frame64,
+    /// x || y, the join of most published NCD baselines. Nothing marks the
+    /// boundary, so a match may run from the end of x into y.
+    Concat,
+    /// x || sep || y.
+    Separator(Separator),
+    /// x and y alternated in `chunk`-byte pieces, the rest of the longer one
+    /// last. Compressors with a small window (gzip's is 32 KiB) then see each
+    /// piece of y next to the matching piece of x, even for large inputs.
+    Interleave { chunk: u32 },
+}
+
+impl Join {
+    /// Every join mode, in the order they are listed to users.
+    pub const ALL: &'static [Join] = &[
+        Join::Frame64,
+        Join::Concat,
+        Join::Separator(Separator::NUL),
+        Join::Interleave {
+            chunk: Join::DEFAULT_CHUNK,
+        },
+    ];
+
+    /// Chunk size of a plain `interleave`.
+    pub const DEFAULT_CHUNK: u32 = 1024;
+    /// Largest accepted interleave chunk (16 MiB).
+    pub const MAX_CHUNK: u32 = 1 << 24;
+
+    /// The name used by the CLI, server and bindings, without parameters.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Join::Frame64 => "frame64",
+            Join::Concat => "concat",
+            Join::Separator(_) => "sep",
+            Join::Interleave { .. } => "interleave",
+        }
+    }
+}
+
+impl fmt::Display for Join {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())?;
+        match self {
+            Join::Separator(sep) => write!(f, ":{sep}"),
+            Join::Interleave { chunk } => write!(f, ":{chunk}"),
+            Join::Frame64 | Join::Concat => Ok(()),
+        }
+    }
+}
+
+impl FromStr for Join {
+    type Err = NcdError;
+
+    fn from_str(s: &str) -> NcdResult<Self> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        let join = Join::ALL
+            .iter()
+            .find(|j| j.name() == name)
+            .copied()
+            .ok_or_else(|| unknown_name("join", s, Join::ALL.iter().map(Join::name)))?;
+
+        match (join, arg) {
+            (_, None) => Ok(join),
+            (Join::Separator(_), Some(hex)) => Ok(Join::Separator(hex.parse()?)),
+            (Join::Interleave { .. }, Some(n)) => {
+                let value: i64 = n.parse().map_err(|_| {
+                    NcdError::invalid(format!(
+                        "join interleave chunk must be an integer, got `{n}`"
+                    ))
+                })?;
+                match u32::try_from(value) {
+                    Ok(chunk @ 1..=Join::MAX_CHUNK) => Ok(Join::Interleave { chunk }),
+                    _ => Err(NcdError::OutOfRange {
+                        name: "join interleave chunk".to_string(),
+                        value,
+                        min: 1,
+                        max: Join::MAX_CHUNK.into(),
+                    }),
+                }
+            }
+            (_, Some(_)) => Err(NcdError::invalid(format!(
+                "join {name} takes no parameter: {s}"
+            ))),
+        }
+    }
+}
+
+/// The 1 to 16 separator bytes of `Join::Separator`, written as hex.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Separator {
+    bytes: [u8; Separator::MAX_LEN],
+    len: u8,
+}
+
+impl Separator {
+    pub const MAX_LEN: usize = 16;
+
+    /// A single NUL byte, which text inputs do not contain.
+    pub const NUL: Separator = Separator {
+        bytes: [0; Separator::MAX_LEN],
+        len: 1,
+    };
+
+    pub fn new(sep: &[u8]) -> NcdResult<Self> {
+        if sep.is_empty() || sep.len() > Self::MAX_LEN {
+            return Err(NcdError::invalid(format!(
+                "join separator must be 1 to {} bytes, got {}",
+                Self::MAX_LEN,
+                sep.len()
+            )));
+        }
+        let mut bytes = [0; Self::MAX_LEN];
+        bytes[..sep.len()].copy_from_slice(sep);
+        Ok(Self {
+            bytes,
+            len: sep.len() as u8,
+        })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..usize::from(self.len)]
+    }
+}
+
+impl fmt::Display for Separator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_bytes()
+            .iter()
+            .try_for_each(|b| write!(f, "{b:02x}"))
+    }
 }
 
-fn frame64_bytes(data: &[u8]) -> Vec<u8> {
-    let mut out = Vec::with_capacity(8 + data.len());
+impl fmt::Debug for Separator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Separator({self})")
+    }
+}
+
+impl FromStr for Separator {
+    type Err = NcdError;
+
+    fn from_str(hex: &str) -> NcdResult<Self> {
+        let bad = || {
+            NcdError::invalid(format!(
+                "join separator must be hex bytes, e.g. sep:0a, got {hex:?}"
+            ))
+        };
+        if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+            return Err(bad());
+        }
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| bad()))
+            .collect::<NcdResult<Vec<u8>>>()?;
+        Separator::new(&bytes)
+    }
+}
+
+/// The shared "unknown option value" error used by every frontend.
+fn unknown_name<'a>(kind: &str, got: &str, expected: impl Iterator<Item = &'a str>) -> NcdError {
+    let expected: Vec<&str> = expected.collect();
+    NcdError::invalid(format!(
+        "unknown {kind}: {got} (expected one of: {})",
+        expected.join(", ")
+    ))
+}
+
+fn frame64_header(data: &[u8]) -> [u8; 8] {
     let len: u64 = data.len().try_into().unwrap_or(u64::MAX);
-    out.extend_from_slice(&len.to_le_bytes());
-    out.extend_from_slice(data);
-    out
+    len.to_le_bytes()
 }
 
 pub fn join_bytes(x: &[u8], y: &[u8], join: Join) -> Vec<u8> {
+    with_join(x, y, join, |px, py| [px, py].concat().concat())
+}
+
+/// Hand join(x, y) to `f` as the parts that depend only on x and the rest,
+/// without concatenating them.
+fn with_join<T>(x: &[u8], y: &[u8], join: Join, f: impl FnOnce(&[&[u8]], &[&[u8]]) -> T) -> T {
     match join {
-        Join::Frame64 => {
-            let fx = frame64_bytes(x);
-            let fy = frame64_bytes(y);
-            let mut out = Vec::with_capacity(fx.len() + fy.len());
-            out.extend_from_slice(&fx);
-            out.extend_from_slice(&fy);
-            out
+        Join::Frame64 => f(&[&frame64_header(x), x], &[&frame64_header(y), y]),
+        Join::Concat => f(&[x], &[y]),
+        Join::Separator(sep) => f(&[x, sep.as_bytes()], &[y]),
+        Join::Interleave { chunk } => {
+            let chunk = chunk.max(1) as usize;
+            let (mut xs, mut ys) = (x.chunks(chunk), y.chunks(chunk));
+            let mut parts = Vec::with_capacity((x.len() + y.len()) / chunk + 2);
+            loop {
+                match (xs.next(), ys.next()) {
+                    (None, None) => break,
+                    (a, b) => parts.extend(a.into_iter().chain(b)),
+                }
+            }
+            // Only the first piece of x precedes every piece of y.
+            let split = usize::from(!x.is_empty());
+            f(&parts[..split], &parts[split..])
         }
     }
 }
 
-pub trait Compressor {
+/// C(join(x, y)), with the joined parts streamed into the compressor instead
+/// of being copied into a fresh buffer.
+pub fn joined_len<C: Compressor + ?Sized>(
+    c: &C,
+    x: &[u8],
+    y: &[u8],
+    join: Join,
+) -> io::Result<usize> {
+    with_join(x, y, join, |px, py| {
+        c.compressed_len_parts(&[px, py].concat())
+    })
+}
+
+/// A `Write` sink that only counts what is written to it.
+///
+/// Compressors only need the length of their output, so they encode into this
+/// rather than into a `Vec` they would immediately drop.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CountingWriter {
+    written: usize,
+}
+
+impl CountingWriter {
+    pub fn written(&self) -> usize {
+        self.written
+    }
+}
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads `parts` one after another, for encoders that pull their input.
+///
+/// Every read fills `buf` as far as the input allows, so an encoder sees the
+/// same chunks however the input is split into parts.
+struct PartsReader<'a> {
+    current: &'a [u8],
+    rest: &'a [&'a [u8]],
+}
+
+impl<'a> PartsReader<'a> {
+    fn new(parts: &'a [&'a [u8]]) -> Self {
+        Self {
+            current: &[],
+            rest: parts,
+        }
+    }
+}
+
+impl Read for PartsReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut n = 0;
+        while n < buf.len() {
+            if self.current.is_empty() {
+                let Some((next, rest)) = self.rest.split_first() else {
+                    break;
+                };
+                (self.current, self.rest) = (next, rest);
+                continue;
+            }
+            n += self.current.read(&mut buf[n..])?;
+        }
+        Ok(n)
+    }
+}
+
+fn write_parts<W: Write>(w: &mut W, parts: &[&[u8]]) -> io::Result<()> {
+    parts.iter().try_for_each(|p| w.write_all(p))
+}
+
+/// A compressor whose output size is used as the complexity estimate C(x).
+///
+/// Compressors must be `Send + Sync` so a single instance can be shared by the
+/// worker threads of the parallel matrix engine. Implementations are expected to
+/// be deterministic: the same input must always yield the same length.
+pub trait Compressor: Send + Sync {
     fn id(&self) -> &'static str;
     fn compressed_len(&self, input: &[u8]) -> io::Result<usize>;
+
+    /// The compressed length of the concatenation of `parts`.
+    ///
+    /// The default copies the parts into one buffer; streaming compressors
+    /// override it to feed the parts to the encoder in turn.
+    fn compressed_len_parts(&self, parts: &[&[u8]]) -> io::Result<usize> {
+        self.compressed_len(&parts.concat())
+    }
+
+    /// A context for compressing many inputs in a row on one thread.
+    ///
+    /// The default just calls back into this compressor. Compressors whose
+    /// encoder setup is costly next to compressing a small input (a zstd
+    /// `CCtx`, a deflate state) return a context that keeps the encoder and
+    /// resets it between inputs.
+    fn context(&self) -> Box<dyn CompressorContext + '_> {
+        Box::new(Stateless(self))
+    }
+}
+
+/// Reusable per-thread compression state, from `Compressor::context`.
+///
+/// A context must report exactly the sizes its compressor does, whatever it
+/// compressed before.
+pub trait CompressorContext: Send {
+    fn compressed_len_parts(&mut self, parts: &[&[u8]]) -> io::Result<usize>;
+
+    fn compressed_len(&mut self, input: &[u8]) -> io::Result<usize> {
+        self.compressed_len_parts(&[input])
+    }
+
+    /// C(prefix || suffix).
+    ///
+    /// Contexts that can snapshot their encoder take a snapshot after `prefix`
+    /// once it repeats and resume from it for as long as calls keep passing
+    /// that prefix, as along a matrix row. Only deflate (gzip) can: its state
+    /// is plain data. A zstd `CCtx` cannot be copied once it has compressed
+    /// anything (`ZSTD_copyCCtx` only works before the first input), and
+    /// compressing y against x as a dictionary gives a different size than
+    /// compressing x || y. The default compresses everything.
+    fn compressed_len_resumed(&mut self, prefix: &[&[u8]], suffix: &[&[u8]]) -> io::Result<usize> {
+        self.compressed_len_parts(&[prefix, suffix].concat())
+    }
+}
+
+struct Stateless<'a, C: ?Sized>(&'a C);
+
+impl<C: Compressor + ?Sized> CompressorContext for Stateless<'_, C> {
+    fn compressed_len_parts(&mut self, parts: &[&[u8]]) -> io::Result<usize> {
+        self.0.compressed_len_parts(parts)
+    }
+}
+
+/// A context whose encoder could not be created: every call fails with the
+/// creation error, so it surfaces as the first size does.
+pub(crate) struct Failed {
+    kind: io::ErrorKind,
+    message: String,
+}
+
+impl Failed {
+    pub(crate) fn new(e: io::Error) -> Self {
+        Self {
+            kind: e.kind(),
+            message: format!("create encoder: {e}"),
+        }
+    }
+}
+
+impl CompressorContext for Failed {
+    fn compressed_len_parts(&mut self, _parts: &[&[u8]]) -> io::Result<usize> {
+        Err(io::Error::new(self.kind, self.message.clone()))
+    }
 }
 
 /// A deterministic gzip compressor.
 ///
-/// We implement gzip via flate2's GzEncoder and set the header fields deterministically.
+/// Sized as a raw deflate stream plus the fixed 18 bytes of a gzip member with
+/// no name, comment or extra field and mtime 0, so the output is deterministic.
+///
+/// The raw variant emits a bare deflate stream, without the 10-byte gzip header
+/// and 8-byte CRC/length trailer.
 pub struct Gzip {
-    level: flate2::Compression,
+    level: u8,
+    raw: bool,
 }
 
 impl Gzip {
     pub fn new(level: u32) -> Self {
         Self {
-            level: flate2::Compression::new(level),
+            level: level.min(9) as u8,
+            raw: false,
+        }
+    }
+
+    pub fn raw(level: u32) -> Self {
+        Self {
+            raw: true,
+            ..Self::new(level)
         }
     }
 }
 
 /// Zstandard compressor.
+///
+/// The raw variant drops the 4-byte magic number and the optional frame header
+/// fields (content size, checksum, dictionary id), leaving the minimal frame
+/// header and block headers.
 pub struct Zstd {
     level: i32,
+    raw: bool,
 }
 
 impl Zstd {
     pub fn new(level: i32) -> Self {
-        Self { level }
+        Self { level, raw: false }
+    }
+
+    pub fn raw(level: i32) -> Self {
+        Self { level, raw: true }
     }
 }
 
 impl Compressor for Zstd {
     fn id(&self) -> &'static str {
-        "zstd"
+        if self.raw { "zstd-raw" } else { "zstd" }
     }
 
     fn compressed_len(&self, input: &[u8]) -> io::Result<usize> {
-        zstd::stream::encode_all(input, self.level)
-            .map(|v| v.len())
-            .map_err(io::Error::other)
+        self.compressed_len_parts(&[input])
+    }
+
+    fn compressed_len_parts(&self, parts: &[&[u8]]) -> io::Result<usize> {
+        self.encoder()?.compressed_len_parts(parts)
+    }
+
+    fn context(&self) -> Box<dyn CompressorContext + '_> {
+        match self.encoder() {
+            Ok(ctx) => Box::new(ctx),
+            Err(e) => Box::new(Failed::new(e)),
+        }
+    }
+}
+
+impl Zstd {
+    fn encoder(&self) -> io::Result<ZstdContext<'static>> {
+        let mut enc = zstd::stream::raw::Encoder::new(self.level)?;
+        if self.raw {
+            use zstd::zstd_safe::{CParameter, FrameFormat};
+            enc.set_parameter(CParameter::Format(FrameFormat::Magicless))?;
+            enc.set_parameter(CParameter::ContentSizeFlag(false))?;
+            enc.set_parameter(CParameter::ChecksumFlag(false))?;
+            enc.set_parameter(CParameter::DictIdFlag(false))?;
+        }
+        Ok(ZstdContext::new(enc, self.raw))
+    }
+}
+
+/// A zstd `CCtx` whose session is reset between inputs, writing into a fixed
+/// scratch buffer.
+///
+/// It does not resume row prefixes (see `compressed_len_resumed`), so zstd
+/// matrices compress every C(xy) in full.
+pub(crate) struct ZstdContext<'a> {
+    enc: zstd::stream::raw::Encoder<'a>,
+    pledge: bool,
+    scratch: Vec<u8>,
+}
+
+impl<'a> ZstdContext<'a> {
+    /// With `pledge`, each input's size is announced up front, which picks the
+    /// same parameters as one-shot compression.
+    pub(crate) fn new(enc: zstd::stream::raw::Encoder<'a>, pledge: bool) -> Self {
+        Self {
+            enc,
+            pledge,
+            scratch: vec![0; 16 * 1024],
+        }
+    }
+}
+
+impl CompressorContext for ZstdContext<'_> {
+    fn compressed_len_parts(&mut self, parts: &[&[u8]]) -> io::Result<usize> {
+        use zstd::stream::raw::{InBuffer, Operation, OutBuffer};
+
+        self.enc.reinit()?;
+        if self.pledge {
+            let total = parts.iter().map(|p| p.len() as u64).sum();
+            self.enc.set_pledged_src_size(Some(total))?;
+        }
+
+        let mut written = 0;
+        for part in parts {
+            let mut input = InBuffer::around(part);
+            while input.pos() < part.len() {
+                let mut out = OutBuffer::around(&mut self.scratch[..]);
+                self.enc.run(&mut input, &mut out)?;
+                written += out.pos();
+            }
+        }
+        loop {
+            let mut out = OutBuffer::around(&mut self.scratch[..]);
+            let remaining = self.enc.finish(&mut out, false)?;
+            written += out.pos();
+            if remaining == 0 {
+                return Ok(written);
+            }
+        }
     }
 }
 
@@ -94,285 +564,598 @@ impl Compressor for Brotli {
     }
 
     fn compressed_len(&self, input: &[u8]) -> io::Result<usize> {
+        self.compressed_len_parts(&[input])
+    }
+
+    fn compressed_len_parts(&self, parts: &[&[u8]]) -> io::Result<usize> {
         use brotli::enc::BrotliEncoderParams;
 
-        let mut out = Vec::new();
         let params = BrotliEncoderParams {
             quality: self.quality as i32,
             lgwin: self.lgwin as i32,
             ..Default::default()
         };
 
-        brotli::BrotliCompress(&mut &input[..], &mut out, &params)
-            .map_err(|e| io::Error::other(format!("brotli: {e:?}")))?;
-
-        Ok(out.len())
+        // `CompressorWriter::into_inner` finishes the stream but drops any error
+        // doing so; `BrotliCompress` reports it.
+        let mut out = CountingWriter::default();
+        brotli::BrotliCompress(&mut PartsReader::new(parts), &mut out, &params)?;
+        Ok(out.written())
     }
 }
 
-/// LZ4 (block) compressor.
+/// LZ4 (block) compressor, via liblz4.
+///
+/// Fast mode trades ratio for speed through `accel`; HC mode (`with_hc`) runs
+/// the LZ4-HC match finder instead. The raw variant counts the bare block,
+/// without the 4-byte length prefix.
 pub struct Lz4 {
     /// acceleration factor: 1 = best compression, higher = faster
     accel: i32,
+    /// LZ4-HC level (1..=12); 0 = fast mode
+    hc: u32,
+    raw: bool,
 }
 
 impl Lz4 {
     pub fn new(accel: i32) -> Self {
-        Self { accel }
+        Self {
+            accel,
+            hc: 0,
+            raw: false,
+        }
+    }
+
+    pub fn raw(accel: i32) -> Self {
+        Self {
+            raw: true,
+            ..Self::new(accel)
+        }
+    }
+
+    /// Use LZ4-HC at `level` (1..=12) instead of fast mode; 0 keeps fast mode.
+    pub fn with_hc(self, level: u32) -> Self {
+        Self { hc: level, ..self }
     }
 }
 
 impl Compressor for Lz4 {
     fn id(&self) -> &'static str {
-        "lz4"
+        if self.raw { "lz4-raw" } else { "lz4" }
     }
 
     fn compressed_len(&self, input: &[u8]) -> io::Result<usize> {
-        // lz4_flex doesn't expose acceleration; it uses a fast algorithm.
-        // We'll treat this as a placeholder for "lz4" and keep determinism.
-        let _ = self.accel;
-        Ok(lz4_flex::compress_prepend_size(input).len())
+        self.buffers().compressed_len(input)
+    }
+
+    fn compressed_len_parts(&self, parts: &[&[u8]]) -> io::Result<usize> {
+        self.buffers().compressed_len_parts(parts)
+    }
+
+    fn context(&self) -> Box<dyn CompressorContext + '_> {
+        Box::new(self.buffers())
+    }
+}
+
+impl Lz4 {
+    fn buffers(&self) -> Lz4Context {
+        use lz4::block::CompressionMode;
+
+        let mode = if self.hc > 0 {
+            CompressionMode::HIGHCOMPRESSION(self.hc as i32)
+        } else {
+            CompressionMode::FAST(self.accel)
+        };
+        Lz4Context {
+            mode,
+            prefix: !self.raw,
+            joined: Vec::new(),
+            out: Vec::new(),
+        }
+    }
+}
+
+/// liblz4's `LZ4_MAX_INPUT_SIZE`.
+const LZ4_MAX_INPUT: usize = 0x7E00_0000;
+
+/// The lz4 block API has no streaming form and needs contiguous input, so its
+/// context keeps the joined input and the output buffer between calls instead.
+struct Lz4Context {
+    mode: lz4::block::CompressionMode,
+    prefix: bool,
+    joined: Vec<u8>,
+    out: Vec<u8>,
+}
+
+impl CompressorContext for Lz4Context {
+    fn compressed_len_parts(&mut self, parts: &[&[u8]]) -> io::Result<usize> {
+        let input = match parts {
+            [one] => one,
+            _ => {
+                self.joined.clear();
+                parts.iter().for_each(|p| self.joined.extend_from_slice(p));
+                &self.joined[..]
+            }
+        };
+        if input.len() > LZ4_MAX_INPUT {
+            return Err(NcdError::InputTooLarge {
+                id: if self.prefix { "lz4" } else { "lz4-raw" },
+                len: input.len(),
+                max: LZ4_MAX_INPUT,
+            }
+            .into());
+        }
+        let bound = lz4::block::compress_bound(input.len())? + 4;
+        if self.out.len() < bound {
+            self.out.resize(bound, 0);
+        }
+        lz4::block::compress_to_buffer(input, Some(self.mode), self.prefix, &mut self.out)
     }
 }
 
 /// XZ (LZMA2) compressor.
+///
+/// The raw variant counts only the LZMA2 data: the stream header and footer,
+/// block header, padding, index and integrity check are all excluded.
 pub struct Xz {
     level: u32,
+    raw: bool,
 }
 
 impl Xz {
     pub fn new(level: u32) -> Self {
-        Self { level }
+        Self { level, raw: false }
+    }
+
+    pub fn raw(level: u32) -> Self {
+        Self { level, raw: true }
     }
 }
 
 impl Compressor for Xz {
     fn id(&self) -> &'static str {
-        "xz"
+        if self.raw { "xz-raw" } else { "xz" }
     }
 
     fn compressed_len(&self, input: &[u8]) -> io::Result<usize> {
+        self.compressed_len_parts(&[input])
+    }
+
+    fn compressed_len_parts(&self, parts: &[&[u8]]) -> io::Result<usize> {
+        use xz2::stream::{Check, Stream};
         use xz2::write::XzEncoder;
 
-        let mut enc = XzEncoder::new(Vec::new(), self.level);
-        enc.write_all(input)?;
-        let out = enc.finish()?;
-        Ok(out.len())
+        if !self.raw {
+            let mut enc = XzEncoder::new(CountingWriter::default(), self.level);
+            write_parts(&mut enc, parts)?;
+            return Ok(enc.finish()?.written());
+        }
+
+        // xz2 has no raw LZMA2 encoder, so encode without a check and read the
+        // payload size back out of the container.
+        let stream = Stream::new_easy_encoder(self.level, Check::None).map_err(io::Error::other)?;
+        let mut enc = XzEncoder::new_stream(XzTail::default(), stream);
+        write_parts(&mut enc, parts)?;
+        enc.finish()?.payload_len()
+    }
+}
+
+/// Sink for raw xz: counts the stream but keeps only what `payload_len` reads,
+/// the first block header byte and the trailing index and footer.
+#[derive(Default)]
+struct XzTail {
+    written: usize,
+    block_header: Option<u8>,
+    tail: Vec<u8>,
+}
+
+impl XzTail {
+    /// Enough for the footer (12) plus a single-record index (at most 24).
+    const KEEP: usize = 64;
+
+    /// Total LZMA2 data size inside an xz stream written without an integrity
+    /// check.
+    ///
+    /// Layout: stream header (12) | block | index | stream footer (12). The
+    /// index record's "unpadded size" is block header + data (+ check, here 0),
+    /// and the block header's first byte encodes its own size. The easy encoder
+    /// writes a single block.
+    fn payload_len(&self) -> io::Result<usize> {
+        xz_payload_len(self.block_header, &self.tail)
+    }
+}
+
+impl Write for XzTail {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.block_header.is_none() && self.written + buf.len() > 12 {
+            self.block_header = Some(buf[12 - self.written.min(12)]);
+        }
+        self.written += buf.len();
+        self.tail.extend_from_slice(buf);
+        if self.tail.len() > 16 * Self::KEEP {
+            self.tail.drain(..self.tail.len() - Self::KEEP);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn xz_payload_len(block_header: Option<u8>, tail: &[u8]) -> io::Result<usize> {
+    let bad = || io::Error::new(io::ErrorKind::InvalidData, "malformed xz stream");
+    let varint = |buf: &[u8], pos: &mut usize| -> io::Result<usize> {
+        let mut v = 0usize;
+        for shift in (0..63).step_by(7) {
+            let b = *buf.get(*pos).ok_or_else(bad)?;
+            *pos += 1;
+            v |= usize::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(bad())
+    };
+
+    let footer = tail.len().checked_sub(12).ok_or_else(bad)?;
+    let backward = u32::from_le_bytes(tail[footer + 4..footer + 8].try_into().map_err(|_| bad())?);
+    let index_len = (backward as usize + 1) * 4;
+    let mut pos = footer.checked_sub(index_len).ok_or_else(bad)?;
+
+    if tail.get(pos) != Some(&0) {
+        return Err(bad());
+    }
+    pos += 1;
+    match varint(tail, &mut pos)? {
+        0 => Ok(0),
+        1 => {
+            let unpadded = varint(tail, &mut pos)?;
+            let header = (usize::from(block_header.ok_or_else(bad)?) + 1) * 4;
+            unpadded.checked_sub(header).ok_or_else(bad)
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "xz stream has more than one block",
+        )),
+    }
+}
+
+/// bzip2 (Burrows-Wheeler) compressor.
+///
+/// Each block of `level` × 100 kB is sorted as a whole, so repeats anywhere
+/// within a block are found regardless of distance.
+pub struct Bzip2 {
+    level: u32,
+}
+
+impl Bzip2 {
+    /// `level` 1..=9 selects the block size.
+    pub fn new(level: u32) -> Self {
+        Self { level }
+    }
+}
+
+impl Compressor for Bzip2 {
+    fn id(&self) -> &'static str {
+        "bzip2"
+    }
+
+    fn compressed_len(&self, input: &[u8]) -> io::Result<usize> {
+        self.compressed_len_parts(&[input])
+    }
+
+    fn compressed_len_parts(&self, parts: &[&[u8]]) -> io::Result<usize> {
+        let mut enc = bzip2::write::BzEncoder::new(
+            CountingWriter::default(),
+            bzip2::Compression::new(self.level),
+        );
+        write_parts(&mut enc, parts)?;
+        Ok(enc.finish()?.written())
+    }
+}
+
+/// PPMd (variant H, as in 7z) context-model compressor.
+///
+/// Predicts each byte from the preceding `order` bytes, which suits text. The
+/// model gets `mem_mb` MiB, allocated per call.
+pub struct Ppmd {
+    order: u32,
+    mem_mb: u32,
+}
+
+impl Ppmd {
+    pub fn new(order: u32, mem_mb: u32) -> Self {
+        Self { order, mem_mb }
+    }
+}
+
+impl Compressor for Ppmd {
+    fn id(&self) -> &'static str {
+        "ppmd"
+    }
+
+    fn compressed_len(&self, input: &[u8]) -> io::Result<usize> {
+        self.compressed_len_parts(&[input])
+    }
+
+    fn compressed_len_parts(&self, parts: &[&[u8]]) -> io::Result<usize> {
+        let mut enc =
+            ppmd_rust::Ppmd7Encoder::new(CountingWriter::default(), self.order, self.mem_mb << 20)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("ppmd: {e}")))?;
+        write_parts(&mut enc, parts)?;
+        // No end marker: as in the 7z container, the input length is known.
+        Ok(enc.finish(false)?.written())
+    }
+}
+
+impl Gzip {
+    /// Header (10 bytes) and CRC/length trailer (8 bytes) of a gzip member
+    /// with no name, comment or extra field.
+    const GZIP_OVERHEAD: usize = 18;
+
+    fn deflate(&self) -> DeflateContext {
+        DeflateContext::new(self.level, if self.raw { 0 } else { Self::GZIP_OVERHEAD })
     }
 }
 
 impl Compressor for Gzip {
     fn id(&self) -> &'static str {
-        "gzip"
+        if self.raw { "gzip-raw" } else { "gzip" }
     }
 
     fn compressed_len(&self, input: &[u8]) -> io::Result<usize> {
-        use flate2::GzBuilder;
+        self.compressed_len_parts(&[input])
+    }
 
-        let mut enc = GzBuilder::new().mtime(0).write(Vec::new(), self.level);
-        enc.write_all(input)?;
-        let out = enc.finish()?;
-        Ok(out.len())
+    fn compressed_len_parts(&self, parts: &[&[u8]]) -> io::Result<usize> {
+        self.deflate().compressed_len_parts(parts)
+    }
+
+    fn context(&self) -> Box<dyn CompressorContext + '_> {
+        Box::new(self.deflate())
     }
 }
 
 pub fn compressor_ids() -> &'static [&'static str] {
-    &["gzip", "zstd", "brotli", "lz4", "xz"]
+    &[
+        "gzip",
+        "zstd",
+        "brotli",
+        "lz4",
+        "xz",
+        "bzip2",
+        "ppmd",
+        "zstd-dict",
+        "gzip-raw",
+        "zstd-raw",
+        "lz4-raw",
+        "xz-raw",
+    ]
 }
 
-#[derive(Clone, Copy, Debug)]
-pub enum Symmetry {
-    None,
-    Min,
+named_enum! {
+    /// How C(xy) and C(yx) are combined into the joined size of a pair.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Symmetry("symmetry") {
+        /// C(xy) only.
+        None => "none",
+        /// min(C(xy), C(yx)).
+        Min => "min",
+        /// (C(xy) + C(yx)) / 2.
+        Avg => "avg",
+        /// max(C(xy), C(yx)).
+        Max => "max",
+    }
+}
+
+impl Symmetry {
+    /// Whether NCD(x, y) == NCD(y, x) under this mode, so matrices over a
+    /// single set can be mirrored instead of computed twice.
+    pub fn is_symmetric(&self) -> bool {
+        match self {
+            Symmetry::None => false,
+            Symmetry::Min | Symmetry::Avg | Symmetry::Max => true,
+        }
+    }
+
+    /// The joined size used for the pair, given C(xy) and C(yx).
+    pub fn combine(&self, cxy: f64, cyx: f64) -> f64 {
+        match self {
+            Symmetry::None => cxy,
+            Symmetry::Min => cxy.min(cyx),
+            Symmetry::Avg => (cxy + cyx) / 2.0,
+            Symmetry::Max => cxy.max(cyx),
+        }
+    }
+}
+
+named_enum! {
+    /// The distance computed from C(x), C(y) and the joined size C(xy).
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Metric("metric") {
+        /// (C(xy) - min(C(x), C(y))) / max(C(x), C(y)), the normalized
+        /// compression distance.
+        Ncd => "ncd",
+        /// C(xy) / (C(x) + C(y)), Keogh et al.'s compression-based
+        /// dissimilarity measure. About 0.5 for identical inputs and 1 for
+        /// unrelated ones.
+        Cdm => "cdm",
+        /// 1 - (C(x) + C(y) - C(xy)) / C(xy), the compression-based metric of
+        /// Li et al.; one minus the shared information relative to the joined
+        /// size.
+        Clm => "clm",
+    }
+}
+
+impl Metric {
+    /// The distance for singleton sizes `cx`, `cy` and joined size `cxy`.
+    ///
+    /// Pairs of empty inputs are at distance 0 under every metric.
+    pub fn distance(&self, cx: f64, cy: f64, cxy: f64) -> f64 {
+        let d = match self {
+            Metric::Ncd => (cxy - cx.min(cy)) / cx.max(cy),
+            Metric::Cdm => cxy / (cx + cy),
+            Metric::Clm => 1.0 - (cx + cy - cxy) / cxy,
+        };
+        if d.is_nan() { 0.0 } else { d }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct NcdOptions {
     pub join: Join,
     pub symmetry: Symmetry,
+    pub metric: Metric,
     pub clamp_0_1: bool,
 }
 
+impl NcdOptions {
+    /// Build options from the by-name settings frontends accept; `None` keeps the default.
+    pub fn from_names(
+        join: Option<&str>,
+        symmetry: Option<&str>,
+        metric: Option<&str>,
+        clamp_0_1: Option<bool>,
+    ) -> NcdResult<Self> {
+        let d = Self::default();
+        Ok(Self {
+            join: join.map_or(Ok(d.join), str::parse)?,
+            symmetry: symmetry.map_or(Ok(d.symmetry), str::parse)?,
+            metric: metric.map_or(Ok(d.metric), str::parse)?,
+            clamp_0_1: clamp_0_1.unwrap_or(d.clamp_0_1),
+        })
+    }
+}
+
 impl Default for NcdOptions {
     fn default() -> Self {
         Self {
             join: Join::Frame64,
             symmetry: Symmetry::Min,
+            metric: Metric::Ncd,
             clamp_0_1: false,
         }
     }
 }
 
-pub fn ncd<C: Compressor + ?Sized>(c: &C, x: &[u8], y: &[u8], opts: NcdOptions) -> io::Result<f64> {
-    let cx = c.compressed_len(x)? as f64;
-    let cy = c.compressed_len(y)? as f64;
-    ncd_from_sizes(c, x, y, cx, cy, opts)
+pub fn ncd<C: Compressor + ?Sized>(c: &C, x: &[u8], y: &[u8], opts: NcdOptions) -> NcdResult<f64> {
+    Ok(ncd_detail(c, x, y, opts)?.distance)
 }
 
-fn ncd_from_sizes<C: Compressor + ?Sized>(
-    c: &C,
-    x: &[u8],
-    y: &[u8],
-    cx: f64,
-    cy: f64,
-    opts: NcdOptions,
-) -> io::Result<f64> {
-    let min = cx.min(cy);
-    let max = cx.max(cy);
-
-    if max == 0.0 {
-        return Ok(0.0);
-    }
+/// Which joined size a distance was computed from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JoinDirection {
+    /// C(xy).
+    Xy,
+    /// C(yx).
+    Yx,
+    /// Both, averaged (`Symmetry::Avg`).
+    Both,
+}
 
-    let cxy = c.compressed_len(&join_bytes(x, y, opts.join))? as f64;
-    let ccat = match opts.symmetry {
-        Symmetry::None => cxy,
-        Symmetry::Min => {
-            let cyx = c.compressed_len(&join_bytes(y, x, opts.join))? as f64;
-            cxy.min(cyx)
+impl JoinDirection {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JoinDirection::Xy => "xy",
+            JoinDirection::Yx => "yx",
+            JoinDirection::Both => "both",
         }
-    };
-
-    let mut d = (ccat - min) / max;
-    if d.is_nan() {
-        d = 0.0;
     }
+}
 
-    if opts.clamp_0_1 {
-        d = d.clamp(0.0, 1.0);
+impl fmt::Display for JoinDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
     }
+}
 
-    Ok(d)
+/// A distance together with the compressed sizes it was computed from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NcdDetail {
+    pub distance: f64,
+    pub cx: usize,
+    pub cy: usize,
+    pub cxy: usize,
+    /// `None` when it was not compressed: under `Symmetry::None`, for the
+    /// distance of a buffer to itself, and when C(x) and C(y) are both 0.
+    pub cyx: Option<usize>,
+    pub direction: JoinDirection,
+    /// Wall time spent compressing. In matrices C(x) and C(y) are shared by a
+    /// whole row and column, so there it covers only C(xy) and C(yx).
+    pub wall: Duration,
 }
 
-/// Compute an NCD matrix between two sets of byte buffers.
-///
-/// This function caches C(x) / C(y) so computing a matrix isn't O((n*m) * compress(x)).
-pub fn ncd_matrix<C: Compressor + ?Sized>(
+/// `ncd` that also reports the compressed sizes behind the distance.
+pub fn ncd_detail<C: Compressor + ?Sized>(
     c: &C,
-    a: &[Vec<u8>],
-    b: &[Vec<u8>],
+    x: &[u8],
+    y: &[u8],
     opts: NcdOptions,
-) -> io::Result<Vec<Vec<f64>>> {
-    // Deduplicate singleton compression sizes using a content hash.
-    // This helps when the same bytes appear multiple times in `a` and/or `b`.
-    let mut size_cache: HashMap<[u8; 32], f64> = HashMap::new();
-
-    let mut a_sizes: Vec<f64> = Vec::with_capacity(a.len());
-    for x in a {
-        let key: [u8; 32] = *blake3::hash(x).as_bytes();
-        let cx = match size_cache.get(&key) {
-            Some(v) => *v,
-            None => {
-                let v = c.compressed_len(x)? as f64;
-                size_cache.insert(key, v);
-                v
-            }
-        };
-        a_sizes.push(cx);
-    }
-
-    let mut b_sizes: Vec<f64> = Vec::with_capacity(b.len());
-    for y in b {
-        let key: [u8; 32] = *blake3::hash(y).as_bytes();
-        let cy = match size_cache.get(&key) {
-            Some(v) => *v,
-            None => {
-                let v = c.compressed_len(y)? as f64;
-                size_cache.insert(key, v);
-                v
-            }
-        };
-        b_sizes.push(cy);
-    }
-
-    let mut out = vec![vec![0.0; b.len()]; a.len()];
-
-    for (i, x) in a.iter().enumerate() {
-        for (j, y) in b.iter().enumerate() {
-            out[i][j] = ncd_from_sizes(c, x, y, a_sizes[i], b_sizes[j], opts)?;
-        }
-    }
-
-    Ok(out)
+) -> NcdResult<NcdDetail> {
+    let start = Instant::now();
+    let mut ctx = c.context();
+    let mut detail = (|| {
+        let cx = ctx.compressed_len(x)?;
+        let cy = ctx.compressed_len(y)?;
+        ncd_from_sizes(&mut *ctx, x, y, cx, cy, opts)
+    })()
+    .map_err(|e| NcdError::compressor(c.id(), e))?;
+    detail.wall = start.elapsed();
+    Ok(detail)
 }
 
-/// Per-cell progress information for `ncd_matrix_with_progress`.
-#[derive(Clone, Copy, Debug)]
-pub struct NcdMatrixProgress {
-    pub done: usize,
-    pub total: usize,
-    /// Rough "work" proxy for estimation; currently `x.len() + y.len()`.
-    pub input_bytes: u64,
-    pub wall: std::time::Duration,
-}
-
-/// Compute an NCD matrix, invoking a callback after each computed cell.
-///
-/// Intended for CLIs to display progress + ETA.
-pub fn ncd_matrix_with_progress<C: Compressor + ?Sized, F>(
-    c: &C,
-    a: &[Vec<u8>],
-    b: &[Vec<u8>],
+pub(crate) fn ncd_from_sizes(
+    ctx: &mut dyn CompressorContext,
+    x: &[u8],
+    y: &[u8],
+    cx: usize,
+    cy: usize,
     opts: NcdOptions,
-    mut on_cell: F,
-) -> io::Result<Vec<Vec<f64>>>
-where
-    F: FnMut(NcdMatrixProgress),
-{
-    // Keep the same caching behavior as `ncd_matrix`.
-    let mut size_cache: HashMap<[u8; 32], f64> = HashMap::new();
-
-    let mut a_sizes: Vec<f64> = Vec::with_capacity(a.len());
-    for x in a {
-        let key: [u8; 32] = *blake3::hash(x).as_bytes();
-        let cx = match size_cache.get(&key) {
-            Some(v) => *v,
-            None => {
-                let v = c.compressed_len(x)? as f64;
-                size_cache.insert(key, v);
-                v
-            }
-        };
-        a_sizes.push(cx);
-    }
-
-    let mut b_sizes: Vec<f64> = Vec::with_capacity(b.len());
-    for y in b {
-        let key: [u8; 32] = *blake3::hash(y).as_bytes();
-        let cy = match size_cache.get(&key) {
-            Some(v) => *v,
-            None => {
-                let v = c.compressed_len(y)? as f64;
-                size_cache.insert(key, v);
-                v
-            }
-        };
-        b_sizes.push(cy);
+) -> io::Result<NcdDetail> {
+    let start = Instant::now();
+    let mut detail = NcdDetail {
+        distance: 0.0,
+        cx,
+        cy,
+        cxy: 0,
+        cyx: None,
+        direction: JoinDirection::Xy,
+        wall: Duration::ZERO,
+    };
+    if cx.max(cy) == 0 {
+        return Ok(detail);
     }
 
-    let total = a.len().saturating_mul(b.len());
-    let mut done = 0usize;
-
-    let mut out = vec![vec![0.0; b.len()]; a.len()];
+    // Along a matrix row x is fixed, so C(xy) can resume from x's encoder state.
+    detail.cxy = with_join(x, y, opts.join, |px, py| ctx.compressed_len_resumed(px, py))?;
+    // Self-distance: join(x, x) is the same buffer in both directions.
+    if opts.symmetry != Symmetry::None && !std::ptr::eq(x, y) {
+        let cyx = with_join(y, x, opts.join, |py, px| {
+            ctx.compressed_len_parts(&[py, px].concat())
+        })?;
+        detail.cyx = Some(cyx);
+        detail.direction = match opts.symmetry {
+            Symmetry::Avg if cyx != detail.cxy => JoinDirection::Both,
+            Symmetry::Min if cyx < detail.cxy => JoinDirection::Yx,
+            Symmetry::Max if cyx > detail.cxy => JoinDirection::Yx,
+            _ => JoinDirection::Xy,
+        };
+    }
 
-    for (i, x) in a.iter().enumerate() {
-        for (j, y) in b.iter().enumerate() {
-            let start = std::time::Instant::now();
-            out[i][j] = ncd_from_sizes(c, x, y, a_sizes[i], b_sizes[j], opts)?;
-            let wall = start.elapsed();
+    let ccat = match detail.cyx {
+        Some(cyx) => opts.symmetry.combine(detail.cxy as f64, cyx as f64),
+        None => detail.cxy as f64,
+    };
+    let mut d = opts.metric.distance(cx as f64, cy as f64, ccat);
 
-            done = done.saturating_add(1);
-            on_cell(NcdMatrixProgress {
-                done,
-                total,
-                input_bytes: (x.len() + y.len()) as u64,
-                wall,
-            });
-        }
+    if opts.clamp_0_1 {
+        d = d.clamp(0.0, 1.0);
     }
 
-    Ok(out)
+    detail.distance = d;
+    detail.wall = start.elapsed();
+    Ok(detail)
 }
 
 pub fn read_all<R: Read>(mut r: R) -> io::Result<Vec<u8>> {
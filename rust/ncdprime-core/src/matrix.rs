@@ -0,0 +1,763 @@
+use crate::{
+    CachedCompressor, Compressor, CompressorContext, CompressorSpec, NcdDetail, NcdError,
+    NcdOptions, NcdResult, SizeCache, ncd_from_sizes,
+};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Execution options for the matrix engine.
+///
+/// These only control *how* cells are scheduled; results are bit-identical to
+/// the serial path regardless of the worker count.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MatrixOptions {
+    /// Worker threads. `0` (the default) = `std::thread::available_parallelism()`,
+    /// `1` = compute everything on the calling thread.
+    pub threads: usize,
+}
+
+impl MatrixOptions {
+    /// Single-threaded execution (the behavior of `ncd_matrix`).
+    pub fn serial() -> Self {
+        Self { threads: 1 }
+    }
+
+    /// The number of worker threads this configuration resolves to.
+    pub fn worker_threads(&self) -> usize {
+        if self.threads == 0 {
+            thread::available_parallelism().map_or(1, |n| n.get())
+        } else {
+            self.threads
+        }
+    }
+
+    pub(crate) fn workers_for(&self, jobs: usize) -> usize {
+        self.worker_threads().min(jobs).max(1)
+    }
+}
+
+/// Per-cell progress information for `ncd_matrix_with_progress`.
+#[derive(Clone, Copy, Debug)]
+pub struct NcdMatrixProgress {
+    /// The cell that just finished and its value.
+    pub row: usize,
+    pub col: usize,
+    pub value: f64,
+    pub done: usize,
+    pub total: usize,
+    /// Rough "work" proxy for estimation; currently `x.len() + y.len()`.
+    pub input_bytes: u64,
+    pub wall: Duration,
+    /// The compressed sizes behind `value`.
+    pub detail: NcdDetail,
+}
+
+/// One compressor context per worker, created once and reused by every job
+/// the worker runs.
+pub(crate) type Contexts<'c> = Vec<Box<dyn CompressorContext + 'c>>;
+
+pub(crate) fn contexts<C: Compressor + ?Sized>(c: &C, workers: usize) -> Contexts<'_> {
+    (0..workers).map(|_| c.context()).collect()
+}
+
+/// Run `job` for every index in `jobs` on one thread per entry of `states`,
+/// each job getting its thread's state.
+///
+/// Results are handed to `sink` on the calling thread (in completion order), so
+/// `sink` can hold non-`Send` state such as a progress printer. The first error
+/// stops the remaining workers and is returned.
+pub(crate) fn run_jobs<T, W, J, S>(
+    jobs: Range<usize>,
+    states: &mut [W],
+    job: J,
+    mut sink: S,
+) -> NcdResult<()>
+where
+    T: Send,
+    W: Send,
+    J: Fn(&mut W, usize) -> NcdResult<T> + Sync,
+    S: FnMut(usize, T),
+{
+    if let [state] = states {
+        for k in jobs {
+            sink(k, job(state, k)?);
+        }
+        return Ok(());
+    }
+
+    let next = AtomicUsize::new(jobs.start);
+    let stop = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        let (tx, rx) = mpsc::channel::<(usize, NcdResult<T>)>();
+
+        for state in states.iter_mut() {
+            let tx = tx.clone();
+            let (next, stop, job, end) = (&next, &stop, &job, jobs.end);
+            s.spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let k = next.fetch_add(1, Ordering::Relaxed);
+                    if k >= end {
+                        break;
+                    }
+                    if tx.send((k, job(state, k))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tx);
+
+        let mut first_err = None;
+        for (k, r) in rx {
+            match r {
+                Ok(v) if first_err.is_none() => sink(k, v),
+                Ok(_) => {}
+                Err(e) => {
+                    stop.store(true, Ordering::Relaxed);
+                    first_err.get_or_insert(e);
+                }
+            }
+        }
+
+        first_err.map_or(Ok(()), Err)
+    })
+}
+
+/// The distinct contents (by blake3 hash) of the items of `a` followed by `b`.
+///
+/// Hashing depends only on the inputs, so it is done once and reused by every
+/// compressor in `ncd_matrix_multi`.
+pub(crate) struct Singletons<'a> {
+    unique: Vec<&'a [u8]>,
+    /// For every item of `a` then `b`, its index into `unique`.
+    slots: Vec<usize>,
+    a_len: usize,
+}
+
+impl<'a> Singletons<'a> {
+    pub(crate) fn new<S: AsRef<[u8]>>(a: &'a [S], b: &'a [S]) -> Self {
+        let mut index: HashMap<[u8; 32], usize> = HashMap::new();
+        let mut unique: Vec<&[u8]> = Vec::new();
+
+        let mut slots = Vec::with_capacity(a.len() + b.len());
+        for x in a.iter().chain(b).map(S::as_ref) {
+            let key: [u8; 32] = *blake3::hash(x).as_bytes();
+            let slot = *index.entry(key).or_insert_with(|| {
+                unique.push(x);
+                unique.len() - 1
+            });
+            slots.push(slot);
+        }
+
+        Self {
+            unique,
+            slots,
+            a_len: a.len(),
+        }
+    }
+
+    /// C(x) for every item of `a` and `b`, compressing each distinct content once.
+    pub(crate) fn sizes(
+        &self,
+        id: &'static str,
+        ctxs: &mut [Box<dyn CompressorContext + '_>],
+    ) -> NcdResult<(Vec<usize>, Vec<usize>)> {
+        let mut sizes = vec![0; self.unique.len()];
+        run_jobs(
+            0..self.unique.len(),
+            ctxs,
+            |ctx, k| {
+                ctx.compressed_len(self.unique[k])
+                    .map_err(|e| NcdError::compressor(id, e))
+            },
+            |k, v| sizes[k] = v,
+        )?;
+
+        let (a_slots, b_slots) = self.slots.split_at(self.a_len);
+        Ok((
+            a_slots.iter().map(|&k| sizes[k]).collect(),
+            b_slots.iter().map(|&k| sizes[k]).collect(),
+        ))
+    }
+}
+
+/// How a square matrix over one set reuses NCD(x, y) = NCD(y, x).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mirror {
+    /// Not a symmetric square matrix: every cell is computed.
+    Off,
+    /// Compute the upper triangle only. Mirrored values are held until their
+    /// row is emitted, which peaks at n²/4 values.
+    Triangle,
+    /// Compute whole rows, mirroring only between rows of the same block, so
+    /// memory stays within `MIRROR_BUDGET_CELLS`. Only for streamed matrices
+    /// too large to mirror in full.
+    Block,
+}
+
+/// How a matrix over `a` × `b` is computed: mirrored when one set is compared
+/// against itself under a symmetric NCD, in full otherwise.
+struct Plan<'a> {
+    a: &'a [Vec<u8>],
+    b: &'a [Vec<u8>],
+    mirror: Mirror,
+    singletons: Singletons<'a>,
+}
+
+impl<'a> Plan<'a> {
+    /// `streaming` callers get rows as they finish and must not pay for the
+    /// whole matrix in memory, so past `MIRROR_BUDGET_CELLS` they mirror
+    /// block by block.
+    fn new(a: &'a [Vec<u8>], b: &'a [Vec<u8>], opts: NcdOptions, streaming: bool) -> Self {
+        let n = a.len();
+        let mirror = match opts.symmetry.is_symmetric() && same_items(a, b) {
+            false => Mirror::Off,
+            true if streaming && n.saturating_mul(n) / 4 > MIRROR_BUDGET_CELLS => Mirror::Block,
+            true => Mirror::Triangle,
+        };
+        let singletons = if mirror == Mirror::Off {
+            Singletons::new(a, b)
+        } else {
+            Singletons::new(a, &[])
+        };
+        Self {
+            a,
+            b,
+            mirror,
+            singletons,
+        }
+    }
+
+    fn run<C: Compressor + ?Sized, F, R>(
+        &self,
+        c: &C,
+        opts: NcdOptions,
+        mopts: &MatrixOptions,
+        known: &HashMap<(usize, usize), f64>,
+        on_cell: F,
+        on_row: R,
+    ) -> NcdResult<()>
+    where
+        F: FnMut(NcdMatrixProgress),
+        R: FnMut(usize, Vec<f64>) -> NcdResult<()>,
+    {
+        if self.mirror == Mirror::Off {
+            return full_rows(c, self, opts, mopts, known, on_cell, on_row);
+        }
+        // Normalize to the upper triangle: a known (i, j) also covers (j, i).
+        let known = known
+            .iter()
+            .map(|(&(i, j), &d)| ((i.min(j), i.max(j)), d))
+            .collect();
+        match self.mirror {
+            Mirror::Triangle => square_rows(c, self, opts, mopts, &known, on_cell, on_row),
+            _ => full_rows(c, self, opts, mopts, &known, on_cell, on_row),
+        }
+    }
+}
+
+/// Compute an NCD matrix between two sets of byte buffers.
+///
+/// This function caches C(x) / C(y) so computing a matrix isn't O((n*m) * compress(x)).
+pub fn ncd_matrix<C: Compressor + ?Sized>(
+    c: &C,
+    a: &[Vec<u8>],
+    b: &[Vec<u8>],
+    opts: NcdOptions,
+) -> NcdResult<Vec<Vec<f64>>> {
+    ncd_matrix_parallel(c, a, b, opts, &MatrixOptions::serial())
+}
+
+/// Compute an NCD matrix, invoking a callback after each computed cell.
+///
+/// Intended for CLIs to display progress + ETA.
+pub fn ncd_matrix_with_progress<C: Compressor + ?Sized, F>(
+    c: &C,
+    a: &[Vec<u8>],
+    b: &[Vec<u8>],
+    opts: NcdOptions,
+    on_cell: F,
+) -> NcdResult<Vec<Vec<f64>>>
+where
+    F: FnMut(NcdMatrixProgress),
+{
+    ncd_matrix_parallel_with_progress(c, a, b, opts, &MatrixOptions::serial(), on_cell)
+}
+
+/// Compute an NCD matrix on a pool of worker threads.
+///
+/// Produces exactly the same values as `ncd_matrix`.
+pub fn ncd_matrix_parallel<C: Compressor + ?Sized>(
+    c: &C,
+    a: &[Vec<u8>],
+    b: &[Vec<u8>],
+    opts: NcdOptions,
+    mopts: &MatrixOptions,
+) -> NcdResult<Vec<Vec<f64>>> {
+    ncd_matrix_parallel_with_progress(c, a, b, opts, mopts, |_| {})
+}
+
+/// Parallel variant of `ncd_matrix_with_progress`.
+///
+/// Singleton sizes and pair cells are computed concurrently, but `on_cell` is
+/// always invoked from the calling thread, in completion order, with a
+/// monotonically increasing `done` count.
+///
+/// If `a` and `b` hold the same items and the symmetry mode is symmetric, this
+/// delegates to `ncd_matrix_square_with_progress`.
+pub fn ncd_matrix_parallel_with_progress<C: Compressor + ?Sized, F>(
+    c: &C,
+    a: &[Vec<u8>],
+    b: &[Vec<u8>],
+    opts: NcdOptions,
+    mopts: &MatrixOptions,
+    on_cell: F,
+) -> NcdResult<Vec<Vec<f64>>>
+where
+    F: FnMut(NcdMatrixProgress),
+{
+    let mut out = Vec::with_capacity(a.len());
+    let known = HashMap::new();
+    Plan::new(a, b, opts, false).run(c, opts, mopts, &known, on_cell, |_, row| {
+        out.push(row);
+        Ok(())
+    })?;
+    Ok(out)
+}
+
+/// Compute the self-distance matrix NCD(items[i], items[j]).
+///
+/// With a symmetric `opts.symmetry` only cells with i <= j are compressed and
+/// the lower triangle is mirrored; diagonal cells compress the self-join once.
+/// Otherwise every cell is computed.
+pub fn ncd_matrix_square<C: Compressor + ?Sized>(
+    c: &C,
+    items: &[Vec<u8>],
+    opts: NcdOptions,
+    mopts: &MatrixOptions,
+) -> NcdResult<Vec<Vec<f64>>> {
+    ncd_matrix_square_with_progress(c, items, opts, mopts, |_| {})
+}
+
+/// `ncd_matrix_square` with a per-cell progress callback.
+///
+/// `total` counts only the cells that are actually computed.
+pub fn ncd_matrix_square_with_progress<C: Compressor + ?Sized, F>(
+    c: &C,
+    items: &[Vec<u8>],
+    opts: NcdOptions,
+    mopts: &MatrixOptions,
+    on_cell: F,
+) -> NcdResult<Vec<Vec<f64>>>
+where
+    F: FnMut(NcdMatrixProgress),
+{
+    ncd_matrix_parallel_with_progress(c, items, items, opts, mopts, on_cell)
+}
+
+/// Compute an NCD matrix and hand each finished row to `on_row`, in row order.
+///
+/// Rows are computed in blocks sized to keep every worker busy, so only a few
+/// rows are held in memory at once and output can start before the matrix is
+/// finished. An error returned by `on_row` aborts the computation and is
+/// returned as is; `NcdError::Cancelled` is the one to use for stopping early.
+///
+/// Square matrices over one set are computed a whole row at a time, so memory
+/// stays bounded: NCD(y, x) is mirrored from NCD(x, y) only when both rows fall
+/// in the same block. Small matrices fit in one block and compress just the
+/// upper triangle, as `ncd_matrix_square` always does.
+pub fn ncd_matrix_rows<C: Compressor + ?Sized, F, R>(
+    c: &C,
+    a: &[Vec<u8>],
+    b: &[Vec<u8>],
+    opts: NcdOptions,
+    mopts: &MatrixOptions,
+    on_cell: F,
+    on_row: R,
+) -> NcdResult<()>
+where
+    F: FnMut(NcdMatrixProgress),
+    R: FnMut(usize, Vec<f64>) -> NcdResult<()>,
+{
+    ncd_matrix_rows_resume(c, a, b, opts, mopts, &HashMap::new(), on_cell, on_row)
+}
+
+/// `ncd_matrix_rows` that skips cells whose values are already `known`
+/// (e.g. loaded from a checkpoint).
+///
+/// Known cells are emitted in their rows but not recompressed and not reported
+/// to `on_cell`; `total` counts only the cells left to compute. For square
+/// matrices a known (i, j) also covers (j, i).
+#[allow(clippy::too_many_arguments)]
+pub fn ncd_matrix_rows_resume<C: Compressor + ?Sized, F, R>(
+    c: &C,
+    a: &[Vec<u8>],
+    b: &[Vec<u8>],
+    opts: NcdOptions,
+    mopts: &MatrixOptions,
+    known: &HashMap<(usize, usize), f64>,
+    on_cell: F,
+    on_row: R,
+) -> NcdResult<()>
+where
+    F: FnMut(NcdMatrixProgress),
+    R: FnMut(usize, Vec<f64>) -> NcdResult<()>,
+{
+    Plan::new(a, b, opts, true).run(c, opts, mopts, known, on_cell, on_row)
+}
+
+/// One compressor's matrix from `ncd_matrix_multi`.
+#[derive(Clone, Debug)]
+pub struct MatrixRun {
+    pub spec: CompressorSpec,
+    /// Rows = a, cols = b.
+    pub matrix: Vec<Vec<f64>>,
+    /// Wall time for this compressor, singleton sizes included.
+    pub elapsed: Duration,
+}
+
+/// Compute one NCD matrix per compressor over the same two sets.
+///
+/// Inputs are hashed and deduplicated once and shared by every compressor, so
+/// comparing compressors costs only the compression itself. Compressors run
+/// one after another (each on `mopts.threads` workers) so `elapsed` times are
+/// comparable. `on_cell` receives the index into `specs` with every progress
+/// update; when `cache` is given, sizes are looked up and recorded there.
+pub fn ncd_matrix_multi<F>(
+    specs: &[CompressorSpec],
+    a: &[Vec<u8>],
+    b: &[Vec<u8>],
+    opts: NcdOptions,
+    mopts: &MatrixOptions,
+    cache: Option<&SizeCache>,
+    on_cell: F,
+) -> NcdResult<Vec<MatrixRun>>
+where
+    F: FnMut(usize, NcdMatrixProgress),
+{
+    let mut matrices = vec![Vec::with_capacity(a.len()); specs.len()];
+    let plan = Plan::new(a, b, opts, false);
+    let elapsed = multi_rows(specs, &plan, opts, mopts, cache, on_cell, |k, _, row| {
+        matrices[k].push(row);
+        Ok(())
+    })?;
+
+    Ok(specs
+        .iter()
+        .zip(matrices)
+        .zip(elapsed)
+        .map(|((spec, matrix), elapsed)| MatrixRun {
+            spec: spec.clone(),
+            matrix,
+            elapsed,
+        })
+        .collect())
+}
+
+/// `ncd_matrix_multi` that hands each finished row to `on_row(k, i, row)`
+/// instead of keeping the matrices, with `k` indexing `specs`.
+///
+/// Each compressor's rows arrive in order, as from `ncd_matrix_rows`, and all
+/// of them before the next compressor starts. Returns each compressor's wall
+/// time, singleton sizes included.
+#[allow(clippy::too_many_arguments)]
+pub fn ncd_matrix_multi_rows<F, R>(
+    specs: &[CompressorSpec],
+    a: &[Vec<u8>],
+    b: &[Vec<u8>],
+    opts: NcdOptions,
+    mopts: &MatrixOptions,
+    cache: Option<&SizeCache>,
+    on_cell: F,
+    on_row: R,
+) -> NcdResult<Vec<Duration>>
+where
+    F: FnMut(usize, NcdMatrixProgress),
+    R: FnMut(usize, usize, Vec<f64>) -> NcdResult<()>,
+{
+    let plan = Plan::new(a, b, opts, true);
+    multi_rows(specs, &plan, opts, mopts, cache, on_cell, on_row)
+}
+
+fn multi_rows<F, R>(
+    specs: &[CompressorSpec],
+    plan: &Plan<'_>,
+    opts: NcdOptions,
+    mopts: &MatrixOptions,
+    cache: Option<&SizeCache>,
+    mut on_cell: F,
+    mut on_row: R,
+) -> NcdResult<Vec<Duration>>
+where
+    F: FnMut(usize, NcdMatrixProgress),
+    R: FnMut(usize, usize, Vec<f64>) -> NcdResult<()>,
+{
+    let known = HashMap::new();
+
+    let mut elapsed = Vec::with_capacity(specs.len());
+    for (k, spec) in specs.iter().enumerate() {
+        let inner = spec.build();
+        let cached = cache.map(|sc| CachedCompressor::new(&*inner, spec, sc));
+        let c: &dyn Compressor = match &cached {
+            Some(cc) => cc,
+            None => &*inner,
+        };
+
+        let start = Instant::now();
+        plan.run(
+            c,
+            opts,
+            mopts,
+            &known,
+            |p| on_cell(k, p),
+            |i, row| on_row(k, i, row),
+        )?;
+        elapsed.push(start.elapsed());
+    }
+    Ok(elapsed)
+}
+
+fn same_items(a: &[Vec<u8>], b: &[Vec<u8>]) -> bool {
+    std::ptr::eq(a, b) || a == b
+}
+
+/// Rows per scheduling block: enough cells to occupy every worker a few times
+/// over, so the barrier at the end of each block costs little.
+fn rows_per_block(workers: usize, cols: usize) -> usize {
+    if workers <= 1 {
+        return 1;
+    }
+    (workers * 4).div_ceil(cols.max(1))
+}
+
+/// Most mirrored values a streamed square matrix holds at once (8 MiB).
+/// Up to 2048 items the whole upper triangle is mirrored; larger matrices
+/// mirror within blocks of rows of this many cells.
+const MIRROR_BUDGET_CELLS: usize = 1 << 20;
+
+fn full_rows<C: Compressor + ?Sized, F, R>(
+    c: &C,
+    plan: &Plan<'_>,
+    opts: NcdOptions,
+    mopts: &MatrixOptions,
+    known: &HashMap<(usize, usize), f64>,
+    mut on_cell: F,
+    mut on_row: R,
+) -> NcdResult<()>
+where
+    F: FnMut(NcdMatrixProgress),
+    R: FnMut(usize, Vec<f64>) -> NcdResult<()>,
+{
+    let (a, b) = (plan.a, plan.b);
+    let (n, m) = (a.len(), b.len());
+    let mirror = plan.mirror == Mirror::Block;
+
+    let workers = mopts.workers_for(n.saturating_mul(m));
+    let block = if mirror {
+        rows_per_block(workers, m).max(MIRROR_BUDGET_CELLS / m.max(1))
+    } else {
+        rows_per_block(workers, m)
+    };
+    // Cells (i, j) with j < i in the same block are copied from (j, i).
+    let mirrored = |i: usize, j: usize| mirror && j < i && i / block == j / block;
+    let lookup = |i: usize, j: usize| {
+        if mirror {
+            known.get(&(i.min(j), i.max(j)))
+        } else {
+            known.get(&(i, j))
+        }
+    };
+
+    let skipped = known
+        .keys()
+        .filter(|&&(i, j)| i < n && j < m)
+        .map(|&(i, j)| {
+            if i == j || !mirror || mirrored(j, i) {
+                1
+            } else {
+                2
+            }
+        })
+        .sum::<usize>();
+    let mirrored_total = if mirror {
+        (0..n)
+            .step_by(block)
+            .map(|r0| {
+                let rows = block.min(n - r0);
+                rows * (rows - 1) / 2
+            })
+            .sum()
+    } else {
+        0
+    };
+    let total = n.saturating_mul(m) - skipped - mirrored_total;
+
+    let singleton_workers = mopts.workers_for(n + m);
+    let mut ctxs = contexts(c, workers.max(singleton_workers));
+    let (a_sizes, b_sizes) = plan
+        .singletons
+        .sizes(c.id(), &mut ctxs[..singleton_workers])?;
+    let b_sizes = if mirror { &a_sizes } else { &b_sizes };
+
+    let mut done = 0usize;
+
+    for r0 in (0..n).step_by(block) {
+        let r1 = (r0 + block).min(n);
+        let mut rows = vec![vec![0.0; m]; r1 - r0];
+
+        run_jobs(
+            r0 * m..r1 * m,
+            &mut ctxs[..workers],
+            |ctx, k| {
+                let (i, j) = (k / m, k % m);
+                if mirrored(i, j) {
+                    // Filled in from (j, i) once the block is done.
+                    return Ok((f64::NAN, None));
+                }
+                if let Some(&d) = lookup(i, j) {
+                    return Ok((d, None));
+                }
+                let detail = ncd_from_sizes(&mut **ctx, &a[i], &b[j], a_sizes[i], b_sizes[j], opts)
+                    .map_err(|e| NcdError::compressor(c.id(), e))?;
+                Ok((detail.distance, Some(detail)))
+            },
+            |k, (d, detail)| {
+                let (i, j) = (k / m, k % m);
+                rows[i - r0][j] = d;
+                let Some(detail) = detail else { return };
+
+                done = done.saturating_add(1);
+                on_cell(NcdMatrixProgress {
+                    row: i,
+                    col: j,
+                    value: d,
+                    done,
+                    total,
+                    input_bytes: (a[i].len() + b[j].len()) as u64,
+                    wall: detail.wall,
+                    detail,
+                });
+            },
+        )?;
+
+        if mirror {
+            for i in r0..r1 {
+                for j in r0..i {
+                    rows[i - r0][j] = rows[j - r0][i];
+                }
+            }
+        }
+        for (i, row) in (r0..).zip(rows) {
+            on_row(i, row)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Number of upper-triangle cells (i <= j) in rows before `i` of an n×n matrix.
+fn triangle_offset(n: usize, i: usize) -> usize {
+    i * n - i * i.saturating_sub(1) / 2
+}
+
+/// Map a row-major index over the upper triangle (including the diagonal)
+/// back to its (i, j) cell.
+fn triangle_cell(n: usize, k: usize) -> (usize, usize) {
+    let (mut lo, mut hi) = (0, n);
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        if triangle_offset(n, mid) <= k {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    (lo, lo + (k - triangle_offset(n, lo)))
+}
+
+fn square_rows<C: Compressor + ?Sized, F, R>(
+    c: &C,
+    plan: &Plan<'_>,
+    opts: NcdOptions,
+    mopts: &MatrixOptions,
+    known: &HashMap<(usize, usize), f64>,
+    mut on_cell: F,
+    mut on_row: R,
+) -> NcdResult<()>
+where
+    F: FnMut(NcdMatrixProgress),
+    R: FnMut(usize, Vec<f64>) -> NcdResult<()>,
+{
+    let items = plan.a;
+    let n = items.len();
+    let skipped = known.keys().filter(|&&(_, j)| j < n).count();
+    let total = n.saturating_mul(n + 1) / 2 - skipped;
+
+    let workers = mopts.workers_for(total);
+    let singleton_workers = mopts.workers_for(n);
+    let mut ctxs = contexts(c, workers.max(singleton_workers));
+    let (sizes, _) = plan
+        .singletons
+        .sizes(c.id(), &mut ctxs[..singleton_workers])?;
+
+    // Size blocks by the average upper-triangle row width.
+    let block = rows_per_block(workers, n.div_ceil(2));
+    let mut done = 0usize;
+
+    // lower[i] accumulates row i's mirrored values for columns < i.
+    let mut lower: Vec<Vec<f64>> = vec![Vec::new(); n];
+
+    for r0 in (0..n).step_by(block) {
+        let r1 = (r0 + block).min(n);
+        // upper[i - r0][j - i] = cell (i, j) for j >= i.
+        let mut upper: Vec<Vec<f64>> = (r0..r1).map(|i| vec![0.0; n - i]).collect();
+
+        run_jobs(
+            triangle_offset(n, r0)..triangle_offset(n, r1),
+            &mut ctxs[..workers],
+            |ctx, k| {
+                let (i, j) = triangle_cell(n, k);
+                if let Some(&d) = known.get(&(i, j)) {
+                    return Ok((d, None));
+                }
+                let detail =
+                    ncd_from_sizes(&mut **ctx, &items[i], &items[j], sizes[i], sizes[j], opts)
+                        .map_err(|e| NcdError::compressor(c.id(), e))?;
+                Ok((detail.distance, Some(detail)))
+            },
+            |k, (d, detail)| {
+                let (i, j) = triangle_cell(n, k);
+                upper[i - r0][j - i] = d;
+                let Some(detail) = detail else { return };
+
+                done = done.saturating_add(1);
+                on_cell(NcdMatrixProgress {
+                    row: i,
+                    col: j,
+                    value: d,
+                    done,
+                    total,
+                    input_bytes: (items[i].len() + items[j].len()) as u64,
+                    wall: detail.wall,
+                    detail,
+                });
+            },
+        )?;
+
+        for (i, up) in (r0..).zip(upper) {
+            for (j, &d) in (i + 1..n).zip(&up[1..]) {
+                lower[j].push(d);
+            }
+            let mut row = std::mem::take(&mut lower[i]);
+            row.extend_from_slice(&up);
+            on_row(i, row)?;
+        }
+    }
+
+    Ok(())
+}
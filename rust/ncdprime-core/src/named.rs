@@ -0,0 +1,58 @@
+/// A fieldless enum whose values frontends name: declares the enum with
+/// `ALL` (every value, in the order they are listed to users), `name()`,
+/// `Display` and a `FromStr` whose error lists `ALL`'s names.
+///
+/// Each variant gives its name and, after `|`, any aliases it also parses
+/// from; `$kind` names the setting in errors ("unknown tie break: ...").
+macro_rules! named_enum {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $ty:ident($kind:literal) {
+            $(
+                $(#[$vmeta:meta])*
+                $variant:ident => $name:literal $(| $alias:literal)*
+            ),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $ty {
+            $(
+                $(#[$vmeta])*
+                $variant,
+            )+
+        }
+
+        impl $ty {
+            /// Every value, in the order they are listed to users.
+            pub const ALL: &'static [$ty] = &[$($ty::$variant),+];
+
+            /// The name used by the CLI, server and bindings.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $($ty::$variant => $name,)+
+                }
+            }
+        }
+
+        impl ::std::fmt::Display for $ty {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                f.write_str(self.name())
+            }
+        }
+
+        impl ::std::str::FromStr for $ty {
+            type Err = $crate::NcdError;
+
+            fn from_str(s: &str) -> $crate::NcdResult<Self> {
+                match s {
+                    $($name $(| $alias)* => Ok($ty::$variant),)+
+                    _ => Err($crate::unknown_name(
+                        $kind,
+                        s,
+                        $ty::ALL.iter().map($ty::name),
+                    )),
+                }
+            }
+        }
+    };
+}
@@ -0,0 +1,74 @@
+use crate::{
+    Compressor, MatrixOptions, NcdError, NcdMatrixProgress, NcdOptions, NcdResult, ncd_matrix_rows,
+};
+use std::cmp::Ordering;
+
+/// A reference item and its distance from a query.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Neighbor {
+    /// Index into the reference set.
+    pub index: usize,
+    pub distance: f64,
+}
+
+/// The `k` references closest to each query, nearest first.
+///
+/// Rows are computed with `ncd_matrix_rows`, so every C(x) is compressed once
+/// and only a few rows of distances are held at a time, however many
+/// references there are. Equal distances are ordered by reference index. Each
+/// query gets `min(k, refs.len())` neighbors.
+pub fn ncd_nearest<C: Compressor + ?Sized>(
+    c: &C,
+    queries: &[Vec<u8>],
+    refs: &[Vec<u8>],
+    k: usize,
+    opts: NcdOptions,
+    mopts: &MatrixOptions,
+) -> NcdResult<Vec<Vec<Neighbor>>> {
+    ncd_nearest_with_progress(c, queries, refs, k, opts, mopts, |_| {})
+}
+
+/// `ncd_nearest` with a per-cell progress callback.
+pub fn ncd_nearest_with_progress<C: Compressor + ?Sized, F>(
+    c: &C,
+    queries: &[Vec<u8>],
+    refs: &[Vec<u8>],
+    k: usize,
+    opts: NcdOptions,
+    mopts: &MatrixOptions,
+    on_cell: F,
+) -> NcdResult<Vec<Vec<Neighbor>>>
+where
+    F: FnMut(NcdMatrixProgress),
+{
+    if k == 0 {
+        return Err(NcdError::invalid("nearest k must be at least 1"));
+    }
+    let mut out = vec![Vec::new(); queries.len()];
+    ncd_matrix_rows(c, queries, refs, opts, mopts, on_cell, |row, values| {
+        out[row] = nearest_in_row(&values, k);
+        Ok(())
+    })?;
+    Ok(out)
+}
+
+fn by_distance(a: &Neighbor, b: &Neighbor) -> Ordering {
+    a.distance
+        .total_cmp(&b.distance)
+        .then(a.index.cmp(&b.index))
+}
+
+/// The `k` smallest values of `row`, by distance then index.
+fn nearest_in_row(row: &[f64], k: usize) -> Vec<Neighbor> {
+    let mut all: Vec<Neighbor> = row
+        .iter()
+        .enumerate()
+        .map(|(index, &distance)| Neighbor { index, distance })
+        .collect();
+    if k < all.len() {
+        all.select_nth_unstable_by(k - 1, by_distance);
+        all.truncate(k);
+    }
+    all.sort_by(by_distance);
+    all
+}
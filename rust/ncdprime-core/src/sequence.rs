@@ -0,0 +1,123 @@
+use crate::matrix::{Singletons, contexts, run_jobs};
+use crate::{
+    Compressor, MatrixOptions, NcdError, NcdMatrixProgress, NcdOptions, NcdResult, ncd_from_sizes,
+};
+use std::ops::Range;
+
+/// NCD between each item and the next: `out[i] = NCD(items[i], items[i + 1])`.
+///
+/// Suited to ordered data (log chunks, successive versions of a file) where a
+/// jump in the series marks a change. Every C(x) is compressed once, even
+/// though each item takes part in two pairs. Fewer than two items give an
+/// empty series.
+pub fn ncd_sequence<C: Compressor + ?Sized, S: AsRef<[u8]>>(
+    c: &C,
+    items: &[S],
+    opts: NcdOptions,
+    mopts: &MatrixOptions,
+) -> NcdResult<Vec<f64>> {
+    ncd_sequence_with_progress(c, items, opts, mopts, |_| {})
+}
+
+/// `ncd_sequence` with a per-cell progress callback; cell `i` is reported as
+/// row `i`, column `i + 1`.
+pub fn ncd_sequence_with_progress<C: Compressor + ?Sized, S: AsRef<[u8]>, F>(
+    c: &C,
+    items: &[S],
+    opts: NcdOptions,
+    mopts: &MatrixOptions,
+    on_cell: F,
+) -> NcdResult<Vec<f64>>
+where
+    F: FnMut(NcdMatrixProgress),
+{
+    let pairs: Vec<(usize, usize)> = (1..items.len()).map(|j| (j - 1, j)).collect();
+    pair_distances(c, items, &pairs, opts, mopts, on_cell)
+}
+
+/// Split `data` into windows of `window` bytes starting every `step` bytes,
+/// for `ncd_sequence` over one large input. The windows borrow from `data`,
+/// so overlapping ones cost no extra memory.
+///
+/// Only windows that fit entirely are returned, so up to `step - 1` trailing
+/// bytes may be left out; data shorter than one window is a single window.
+pub fn sliding_windows(data: &[u8], window: usize, step: usize) -> NcdResult<Vec<&[u8]>> {
+    if window == 0 || step == 0 {
+        return Err(NcdError::invalid(format!(
+            "sliding windows need a positive size and step, got {window} and {step}"
+        )));
+    }
+    if data.len() <= window {
+        return Ok(vec![data]);
+    }
+    Ok((0..=data.len() - window)
+        .step_by(step)
+        .map(|start| &data[start..start + window])
+        .collect())
+}
+
+/// NCD(items[i], items[j]) for each `(i, j)` of `pairs`, in order.
+///
+/// Singleton sizes are computed once per distinct item up front, however many
+/// pairs it appears in. Consecutive pairs with the same `i` run as one job on
+/// one worker, so a context that resumes C(xy) from x's encoder state sees
+/// that prefix in a row.
+pub(crate) fn pair_distances<C: Compressor + ?Sized, S: AsRef<[u8]>, F>(
+    c: &C,
+    items: &[S],
+    pairs: &[(usize, usize)],
+    opts: NcdOptions,
+    mopts: &MatrixOptions,
+    mut on_cell: F,
+) -> NcdResult<Vec<f64>>
+where
+    F: FnMut(NcdMatrixProgress),
+{
+    let items: Vec<&[u8]> = items.iter().map(S::as_ref).collect();
+    let total = pairs.len();
+    let mut groups: Vec<Range<usize>> = Vec::new();
+    for (k, &(i, _)) in pairs.iter().enumerate() {
+        match groups.last_mut() {
+            Some(g) if pairs[g.start].0 == i => g.end = k + 1,
+            _ => groups.push(k..k + 1),
+        }
+    }
+    let workers = mopts.workers_for(groups.len());
+    let singleton_workers = mopts.workers_for(items.len());
+    let mut ctxs = contexts(c, workers.max(singleton_workers));
+    let (sizes, _) = Singletons::new(&items, &[]).sizes(c.id(), &mut ctxs[..singleton_workers])?;
+
+    let mut out = vec![0.0; total];
+    let mut done = 0usize;
+    run_jobs(
+        0..groups.len(),
+        &mut ctxs[..workers],
+        |ctx, g| {
+            pairs[groups[g].clone()]
+                .iter()
+                .map(|&(i, j)| {
+                    ncd_from_sizes(&mut **ctx, items[i], items[j], sizes[i], sizes[j], opts)
+                        .map_err(|e| NcdError::compressor(c.id(), e))
+                })
+                .collect::<NcdResult<Vec<_>>>()
+        },
+        |g, details| {
+            for (k, detail) in groups[g].clone().zip(details) {
+                let (i, j) = pairs[k];
+                out[k] = detail.distance;
+                done += 1;
+                on_cell(NcdMatrixProgress {
+                    row: i,
+                    col: j,
+                    value: detail.distance,
+                    done,
+                    total,
+                    input_bytes: (items[i].len() + items[j].len()) as u64,
+                    wall: detail.wall,
+                    detail,
+                });
+            }
+        },
+    )?;
+    Ok(out)
+}
@@ -0,0 +1,235 @@
+use crate::{NcdError, NcdResult};
+
+named_enum! {
+    /// How `build_tree` turns a distance matrix into a tree.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum TreeMethod("tree method") {
+        /// Average linkage: cluster distances are the mean over all item
+        /// pairs. Also accepted as `average`.
+        Upgma => "upgma" | "average",
+        /// Single linkage: the closest pair of items.
+        Single => "single",
+        /// Complete linkage: the farthest pair of items.
+        Complete => "complete",
+        /// Saitou and Nei's neighbor joining. Does not assume a molecular
+        /// clock, so the tree is unrooted (a three-way split at the top).
+        Nj => "nj",
+    }
+}
+
+/// A tree over the items of a distance matrix.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Tree {
+    /// An item, by its index into the matrix.
+    Leaf(usize),
+    /// An inner node and its children, each with the length of its branch.
+    Node(Vec<(Tree, f64)>),
+}
+
+impl Tree {
+    /// The item indices in the order they appear in the tree.
+    pub fn leaves(&self) -> Vec<usize> {
+        let mut out = Vec::new();
+        self.collect_leaves(&mut out);
+        out
+    }
+
+    fn collect_leaves(&self, out: &mut Vec<usize>) {
+        match self {
+            Tree::Leaf(i) => out.push(*i),
+            Tree::Node(children) => children.iter().for_each(|(c, _)| c.collect_leaves(out)),
+        }
+    }
+
+    /// The tree in Newick format, naming leaf `i` by `labels[i]`.
+    ///
+    /// Labels that contain Newick punctuation or whitespace are single-quoted.
+    pub fn to_newick<S: AsRef<str>>(&self, labels: &[S]) -> String {
+        let mut out = String::new();
+        self.write_newick(labels, &mut out);
+        out.push(';');
+        out
+    }
+
+    fn write_newick<S: AsRef<str>>(&self, labels: &[S], out: &mut String) {
+        match self {
+            Tree::Leaf(i) => out.push_str(&newick_label(labels[*i].as_ref())),
+            Tree::Node(children) => {
+                out.push('(');
+                for (k, (child, len)) in children.iter().enumerate() {
+                    if k > 0 {
+                        out.push(',');
+                    }
+                    child.write_newick(labels, out);
+                    out.push_str(&format!(":{len:.6}"));
+                }
+                out.push(')');
+            }
+        }
+    }
+}
+
+fn newick_label(label: &str) -> String {
+    let plain = !label.is_empty()
+        && !label
+            .chars()
+            .any(|c| c.is_whitespace() || "()[]':;,".contains(c));
+    if plain {
+        label.to_string()
+    } else {
+        format!("'{}'", label.replace('\'', "''"))
+    }
+}
+
+/// Build a tree from a square distance matrix such as `ncd_matrix_square`'s.
+///
+/// An asymmetric matrix (e.g. from `Symmetry::None`) is symmetrized by
+/// averaging d(i, j) and d(j, i). Negative branch lengths, which neighbor
+/// joining can produce on non-additive distances, are clamped to 0. Ties are
+/// broken by the lowest item index, so equal inputs give equal trees. NaN and
+/// infinite distances are rejected.
+pub fn build_tree(d: &[Vec<f64>], method: TreeMethod) -> NcdResult<Tree> {
+    let n = d.len();
+    if n == 0 {
+        return Err(NcdError::invalid("cannot build a tree over 0 items"));
+    }
+    if let Some(row) = d.iter().position(|row| row.len() != n) {
+        return Err(NcdError::invalid(format!(
+            "tree needs a square matrix: row {row} has {} values, expected {n}",
+            d[row].len()
+        )));
+    }
+    if let Some((i, j)) = (0..n)
+        .flat_map(|i| (0..n).map(move |j| (i, j)))
+        .find(|&(i, j)| !d[i][j].is_finite())
+    {
+        return Err(NcdError::invalid(format!(
+            "tree needs finite distances: ({i}, {j}) is {}",
+            d[i][j]
+        )));
+    }
+    let d: Vec<Vec<f64>> = (0..n)
+        .map(|i| (0..n).map(|j| (d[i][j] + d[j][i]) / 2.0).collect())
+        .collect();
+
+    Ok(match method {
+        TreeMethod::Nj => neighbor_joining(d),
+        linkage => agglomerate(d, linkage),
+    })
+}
+
+/// The pair (i, j), i < j, of `active` clusters minimizing `score`.
+///
+/// Always one of the active pairs, even if every score is NaN or infinite.
+fn closest(active: &[usize], score: impl Fn(usize, usize) -> f64) -> (usize, usize) {
+    let mut best: Option<(usize, usize, f64)> = None;
+    for (a, &i) in active.iter().enumerate() {
+        for &j in &active[a + 1..] {
+            let s = score(i, j);
+            if best.is_none_or(|(_, _, b)| s < b || b.is_nan()) {
+                best = Some((i, j, s));
+            }
+        }
+    }
+    let (i, j, _) = best.expect("at least two active clusters");
+    (i, j)
+}
+
+/// Hierarchical clustering. Each merge sits at half its cluster distance, so
+/// the path between two items through their merge is that distance.
+fn agglomerate(mut d: Vec<Vec<f64>>, method: TreeMethod) -> Tree {
+    let n = d.len();
+    // Cluster k (initially item k): its subtree, height and item count.
+    let mut trees: Vec<Option<Tree>> = (0..n).map(|i| Some(Tree::Leaf(i))).collect();
+    let mut heights = vec![0.0; n];
+    let mut sizes = vec![1usize; n];
+    let mut active: Vec<usize> = (0..n).collect();
+
+    while active.len() > 1 {
+        let (i, j) = closest(&active, |i, j| d[i][j]);
+        let height = d[i][j] / 2.0;
+        let node = Tree::Node(vec![
+            (
+                trees[i].take().expect("active"),
+                (height - heights[i]).max(0.0),
+            ),
+            (
+                trees[j].take().expect("active"),
+                (height - heights[j]).max(0.0),
+            ),
+        ]);
+
+        // The merged cluster takes over slot i.
+        for &k in &active {
+            if k == i || k == j {
+                continue;
+            }
+            let merged = match method {
+                TreeMethod::Single => d[i][k].min(d[j][k]),
+                TreeMethod::Complete => d[i][k].max(d[j][k]),
+                _ => {
+                    let (si, sj) = (sizes[i] as f64, sizes[j] as f64);
+                    (si * d[i][k] + sj * d[j][k]) / (si + sj)
+                }
+            };
+            d[i][k] = merged;
+            d[k][i] = merged;
+        }
+        trees[i] = Some(node);
+        heights[i] = height;
+        sizes[i] += sizes[j];
+        active.retain(|&k| k != j);
+    }
+
+    trees[active[0]].take().expect("root")
+}
+
+fn neighbor_joining(mut d: Vec<Vec<f64>>) -> Tree {
+    let n = d.len();
+    let mut trees: Vec<Option<Tree>> = (0..n).map(|i| Some(Tree::Leaf(i))).collect();
+    let mut active: Vec<usize> = (0..n).collect();
+
+    while active.len() > 3 {
+        let r = active.len() as f64;
+        let sums: Vec<f64> = (0..n)
+            .map(|i| active.iter().map(|&k| d[i][k]).sum())
+            .collect();
+        let (i, j) = closest(&active, |i, j| (r - 2.0) * d[i][j] - sums[i] - sums[j]);
+
+        let li = d[i][j] / 2.0 + (sums[i] - sums[j]) / (2.0 * (r - 2.0));
+        let lj = d[i][j] - li;
+        let node = Tree::Node(vec![
+            (trees[i].take().expect("active"), li.max(0.0)),
+            (trees[j].take().expect("active"), lj.max(0.0)),
+        ]);
+
+        for &k in &active {
+            if k != i && k != j {
+                let dk = (d[i][k] + d[j][k] - d[i][j]) / 2.0;
+                d[i][k] = dk;
+                d[k][i] = dk;
+            }
+        }
+        trees[i] = Some(node);
+        active.retain(|&k| k != j);
+    }
+
+    match active[..] {
+        [a] => trees[a].take().expect("root"),
+        [a, b] => Tree::Node(vec![
+            (trees[a].take().expect("active"), d[a][b] / 2.0),
+            (trees[b].take().expect("active"), d[a][b] / 2.0),
+        ]),
+        [a, b, c] => {
+            let la = (d[a][b] + d[a][c] - d[b][c]) / 2.0;
+            let lb = d[a][b] - la;
+            let lc = d[a][c] - la;
+            Tree::Node(vec![
+                (trees[a].take().expect("active"), la.max(0.0)),
+                (trees[b].take().expect("active"), lb.max(0.0)),
+                (trees[c].take().expect("active"), lc.max(0.0)),
+            ])
+        }
+        _ => unreachable!("joined down to at most 3 clusters"),
+    }
+}
@@ -0,0 +1,229 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ncdprime_core::{
+    AnomalyOptions, Compressor, CompressorContext, Gzip, MatrixOptions, NcdError, NcdOptions,
+    Symmetry, Threshold, ncd, ncd_anomalies, ncd_anomalies_with_progress,
+};
+
+mod common;
+use common::CountingCompressor;
+
+/// Log-like chunks with one chunk of noise at `odd`.
+fn chunks(n: usize, odd: usize) -> Vec<Vec<u8>> {
+    let mut state = 0x2545_f491_u32;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state
+    };
+    let words = [
+        "GET",
+        "POST",
+        "/home",
+        "/login",
+        "/api/items",
+        "200",
+        "302",
+        "404",
+    ];
+    (0..n)
+        .map(|i| {
+            if i == odd {
+                return (0..600).map(|_| next() as u8).collect();
+            }
+            (0..20)
+                .map(|_| {
+                    let w = |r: u32| words[r as usize % words.len()];
+                    format!(
+                        "INFO {} {} {} user={}\n",
+                        w(next()),
+                        w(next()),
+                        w(next()),
+                        next() % 50
+                    )
+                })
+                .collect::<String>()
+                .into_bytes()
+        })
+        .collect()
+}
+
+#[test]
+fn flags_the_odd_chunk() {
+    let items = chunks(16, 11);
+    for &threshold in Threshold::ALL {
+        let aopts = AnomalyOptions {
+            threshold,
+            ..AnomalyOptions::default()
+        };
+        let scores = ncd_anomalies(
+            &Gzip::new(6),
+            &items,
+            NcdOptions::default(),
+            &aopts,
+            &MatrixOptions { threads: 2 },
+        )
+        .unwrap();
+        assert_eq!(scores.len(), items.len() - 1);
+        assert_eq!(scores[0].index, 1);
+
+        // The noise stands far above anything the ordinary chunks score.
+        let odd = &scores[10];
+        assert_eq!(odd.index, 11);
+        assert!(odd.anomalous, "{threshold}: {scores:?}");
+        for s in scores.iter().filter(|s| s.index != 11) {
+            assert!(s.score * 5.0 < odd.score, "{threshold}: {s:?} vs {odd:?}");
+        }
+    }
+}
+
+#[test]
+fn distance_is_the_mean_over_the_baseline() {
+    let c = Gzip::new(6);
+    let items = chunks(6, 99);
+    let opts = NcdOptions::default();
+    let aopts = AnomalyOptions {
+        baseline: 3,
+        ..AnomalyOptions::default()
+    };
+    let scores = ncd_anomalies(&c, &items, opts, &aopts, &MatrixOptions::serial()).unwrap();
+
+    for s in &scores {
+        let i = s.index;
+        let baseline = i.saturating_sub(3)..i;
+        let want = baseline
+            .clone()
+            .map(|j| ncd(&c, &items[i], &items[j], opts).unwrap())
+            .sum::<f64>()
+            / baseline.len() as f64;
+        assert!((s.distance - want).abs() < 1e-12, "item {i}");
+    }
+    // Too little history to score.
+    assert_eq!((scores[0].score, scores[1].score), (0.0, 0.0));
+}
+
+#[test]
+fn baselines_reuse_singleton_sizes() {
+    let c = CountingCompressor::default();
+    let items: Vec<Vec<u8>> = (0..6).map(|i| vec![b'a' + i; 10]).collect();
+    let opts = NcdOptions {
+        symmetry: Symmetry::None,
+        ..NcdOptions::default()
+    };
+    let aopts = AnomalyOptions {
+        baseline: 2,
+        ..AnomalyOptions::default()
+    };
+    ncd_anomalies(&c, &items, opts, &aopts, &MatrixOptions::serial()).unwrap();
+    // 6 singletons plus one joined pair per (baseline item, item): 1 + 2 * 4.
+    assert_eq!(c.calls(), 6 + 9);
+}
+
+#[test]
+fn cells_put_the_item_before_its_baseline() {
+    let c = Gzip::new(6);
+    let items = chunks(4, 99);
+    let opts = NcdOptions {
+        symmetry: Symmetry::None,
+        ..NcdOptions::default()
+    };
+    let aopts = AnomalyOptions {
+        baseline: 2,
+        ..AnomalyOptions::default()
+    };
+    let mut cells = Vec::new();
+    ncd_anomalies_with_progress(&c, &items, opts, &aopts, &MatrixOptions::serial(), |p| {
+        cells.push((p.row, p.col, p.value));
+    })
+    .unwrap();
+    let pairs: Vec<(usize, usize)> = cells.iter().map(|&(i, j, _)| (i, j)).collect();
+    assert_eq!(pairs, [(1, 0), (2, 0), (2, 1), (3, 1), (3, 2)]);
+    for (i, j, value) in cells {
+        let want = ncd(&c, &items[i], &items[j], opts).unwrap();
+        assert_eq!(value.to_bits(), want.to_bits(), "({i}, {j})");
+    }
+}
+
+/// Counts how often a context sees a C(xy) prefix other than its last one,
+/// i.e. how often a resuming compressor would compress the prefix afresh.
+#[derive(Default)]
+struct PrefixSwitches(AtomicUsize);
+
+struct PrefixContext<'a> {
+    switches: &'a AtomicUsize,
+    last: Vec<u8>,
+}
+
+impl Compressor for PrefixSwitches {
+    fn id(&self) -> &'static str {
+        "prefix-switches"
+    }
+
+    fn compressed_len(&self, input: &[u8]) -> std::io::Result<usize> {
+        Ok(input.len())
+    }
+
+    fn context(&self) -> Box<dyn CompressorContext + '_> {
+        Box::new(PrefixContext {
+            switches: &self.0,
+            last: Vec::new(),
+        })
+    }
+}
+
+impl CompressorContext for PrefixContext<'_> {
+    fn compressed_len_parts(&mut self, parts: &[&[u8]]) -> std::io::Result<usize> {
+        Ok(parts.iter().map(|p| p.len()).sum())
+    }
+
+    fn compressed_len_resumed(
+        &mut self,
+        prefix: &[&[u8]],
+        suffix: &[&[u8]],
+    ) -> std::io::Result<usize> {
+        // Slow enough that every worker gets cells.
+        std::thread::sleep(std::time::Duration::from_micros(200));
+        let prefix = prefix.concat();
+        if prefix != self.last {
+            self.switches.fetch_add(1, Ordering::Relaxed);
+            self.last = prefix;
+        }
+        Ok(self.last.len() + suffix.iter().map(|p| p.len()).sum::<usize>())
+    }
+}
+
+#[test]
+fn an_items_baseline_runs_on_one_worker() {
+    let items = chunks(40, 99);
+    let c = PrefixSwitches::default();
+    ncd_anomalies(
+        &c,
+        &items,
+        NcdOptions::default(),
+        &AnomalyOptions::default(),
+        &MatrixOptions { threads: 4 },
+    )
+    .unwrap();
+    // One prefix per item with a baseline, however the items were spread.
+    assert_eq!(c.0.load(Ordering::Relaxed), items.len() - 1);
+}
+
+#[test]
+fn rejects_an_empty_baseline() {
+    let aopts = AnomalyOptions {
+        baseline: 0,
+        ..AnomalyOptions::default()
+    };
+    let err = ncd_anomalies(
+        &Gzip::new(6),
+        &chunks(3, 99),
+        NcdOptions::default(),
+        &aopts,
+        &MatrixOptions::serial(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, NcdError::InvalidSpec(_)), "{err:?}");
+    assert_eq!("mad".parse::<Threshold>().unwrap(), Threshold::Mad);
+    assert!("iqr".parse::<Threshold>().is_err());
+}
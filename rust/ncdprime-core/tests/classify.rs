@@ -0,0 +1,129 @@
+use ncdprime_core::{
+    Confusion, Gzip, KnnOptions, MatrixOptions, NcdError, NcdOptions, Neighbor, TieBreak, Vote,
+    knn_vote, ncd_classify,
+};
+
+fn neighbors(distances: &[(usize, f64)]) -> Vec<Neighbor> {
+    distances
+        .iter()
+        .map(|&(index, distance)| Neighbor { index, distance })
+        .collect()
+}
+
+fn knn(k: usize, vote: Vote, tie_break: TieBreak) -> KnnOptions {
+    KnnOptions { k, vote, tie_break }
+}
+
+#[test]
+fn names_round_trip() {
+    for &v in Vote::ALL {
+        assert_eq!(v.name().parse::<Vote>().unwrap(), v);
+    }
+    for &t in TieBreak::ALL {
+        assert_eq!(t.name().parse::<TieBreak>().unwrap(), t);
+    }
+    assert!("plurality".parse::<Vote>().is_err());
+    assert!("random".parse::<TieBreak>().is_err());
+}
+
+#[test]
+fn majority_and_distance_votes() {
+    // Items 0 and 1 are class 0; item 2 is class 1 and much nearer.
+    let labels = [0, 0, 1];
+    let n = neighbors(&[(2, 0.05), (0, 0.6), (1, 0.7)]);
+    assert_eq!(
+        knn_vote(&n, &labels, &knn(3, Vote::Majority, TieBreak::Nearest)),
+        Some(0)
+    );
+    assert_eq!(
+        knn_vote(&n, &labels, &knn(3, Vote::Distance, TieBreak::Nearest)),
+        Some(1)
+    );
+    // Only the first k neighbors vote.
+    assert_eq!(
+        knn_vote(&n, &labels, &knn(1, Vote::Majority, TieBreak::Nearest)),
+        Some(1)
+    );
+}
+
+#[test]
+fn tie_breaks() {
+    // Classes 2 and 1 each get two votes; class 2 has the nearest neighbor,
+    // and dropping the farthest (class 2) leaves class 1 ahead.
+    let labels = [2, 1, 1, 2, 0];
+    let n = neighbors(&[(0, 0.1), (1, 0.2), (2, 0.3), (3, 0.4)]);
+    let vote = |tie_break| knn_vote(&n, &labels, &knn(4, Vote::Majority, tie_break));
+    assert_eq!(vote(TieBreak::Nearest), Some(2));
+    assert_eq!(vote(TieBreak::Shrink), Some(1));
+    assert_eq!(vote(TieBreak::First), Some(1));
+}
+
+#[test]
+fn nobody_voting_is_no_class() {
+    let labels = [0, 1];
+    let n = neighbors(&[(0, 0.1), (1, 0.2)]);
+    for &tie_break in TieBreak::ALL {
+        for &vote in Vote::ALL {
+            assert_eq!(knn_vote(&[], &labels, &knn(3, vote, tie_break)), None);
+            assert_eq!(knn_vote(&n, &labels, &knn(0, vote, tie_break)), None);
+        }
+    }
+}
+
+#[test]
+fn confusion_metrics() {
+    let actual = [0, 0, 0, 1, 1, 2];
+    let predicted = [0, 0, 1, 1, 0, 1];
+    let m = Confusion::from_predictions(3, &actual, &predicted);
+    assert_eq!(m.counts, [vec![2, 1, 0], vec![1, 1, 0], vec![0, 1, 0]]);
+    assert_eq!((m.correct(), m.total()), (3, 6));
+    assert_eq!(m.accuracy(), 0.5);
+    assert_eq!(m.precision(0), 2.0 / 3.0);
+    assert_eq!(m.recall(0), 2.0 / 3.0);
+    assert_eq!(m.precision(1), 1.0 / 3.0);
+    assert_eq!(m.recall(1), 0.5);
+    // Class 2 is never predicted and never right.
+    assert_eq!((m.precision(2), m.recall(2), m.support(2)), (0.0, 0.0, 1));
+}
+
+#[test]
+fn classifies_by_compression() {
+    let text = |s: &str| s.repeat(5).into_bytes();
+    let train = vec![
+        text("the cat sat on the mat. "),
+        text("a dog barked at the cat. "),
+        text("0x1f 0x2e 0x3d 0x4c 0x5b "),
+        text("0xa1 0xb2 0xc3 0xd4 0xe5 "),
+    ];
+    let labels = [0, 0, 1, 1];
+    let test = vec![
+        text("the dog sat on the cat. "),
+        text("0x1a 0x2b 0x3c 0x4d 0x5e "),
+    ];
+    let opts = NcdOptions::default();
+    let mopts = MatrixOptions::serial();
+    for &vote in Vote::ALL {
+        for &tie_break in TieBreak::ALL {
+            let kopts = knn(3, vote, tie_break);
+            let got = ncd_classify(&Gzip::new(9), &train, &labels, &test, opts, &kopts, &mopts);
+            assert_eq!(got.unwrap(), [0, 1], "{vote} {tie_break}");
+        }
+    }
+
+    let err = ncd_classify(
+        &Gzip::new(9),
+        &train,
+        &labels[..3],
+        &test,
+        opts,
+        &KnnOptions::default(),
+        &mopts,
+    )
+    .unwrap_err();
+    assert!(matches!(err, NcdError::InvalidSpec(_)), "{err:?}");
+
+    let kopts = knn(0, Vote::Majority, TieBreak::Nearest);
+    let err =
+        ncd_classify(&Gzip::new(9), &train, &labels, &test, opts, &kopts, &mopts).unwrap_err();
+    assert!(matches!(err, NcdError::InvalidSpec(_)), "{err:?}");
+}
@@ -0,0 +1,66 @@
+//! Fixtures shared by the integration tests. Each test crate uses only some
+//! of them.
+#![allow(dead_code)]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ncdprime_core::Compressor;
+
+/// A "compressor" whose size is the input length, counting how often it runs.
+#[derive(Default)]
+pub struct CountingCompressor {
+    calls: AtomicUsize,
+}
+
+impl CountingCompressor {
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::Relaxed)
+    }
+}
+
+impl Compressor for CountingCompressor {
+    fn id(&self) -> &'static str {
+        "count"
+    }
+
+    fn compressed_len(&self, input: &[u8]) -> std::io::Result<usize> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        Ok(input.len())
+    }
+}
+
+/// `n` words of repetitive English-like text; `seed` shifts the word order.
+pub fn prose(seed: u32, n: u32) -> Vec<u8> {
+    const WORDS: [&str; 12] = [
+        "the",
+        "river",
+        "slowly",
+        "carried",
+        "boats",
+        "past",
+        "old",
+        "mills",
+        "and",
+        "quiet",
+        "towns",
+        "downstream",
+    ];
+    (0..n)
+        .map(|i| WORDS[((i * 7 + seed) % WORDS.len() as u32) as usize])
+        .collect::<Vec<_>>()
+        .join(" ")
+        .into_bytes()
+}
+
+/// `n` incompressible xorshift bytes generated from `seed`.
+pub fn noise(seed: u32, n: usize) -> Vec<u8> {
+    let mut x = seed | 1;
+    (0..n)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            x as u8
+        })
+        .collect()
+}
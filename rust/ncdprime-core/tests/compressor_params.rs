@@ -0,0 +1,110 @@
+use ncdprime_core::{CompressorSpec, compressor_ids, compressor_params};
+
+fn parse_err(s: &str) -> String {
+    s.parse::<CompressorSpec>().unwrap_err().to_string()
+}
+
+#[test]
+fn from_params_uses_defaults_and_overrides() {
+    let spec = CompressorSpec::from_params("brotli", []).unwrap();
+    assert_eq!(
+        spec,
+        CompressorSpec::Brotli {
+            quality: 11,
+            lgwin: 22
+        }
+    );
+
+    let spec = CompressorSpec::from_params("brotli", [("lgwin", 16)]).unwrap();
+    assert_eq!(
+        spec,
+        CompressorSpec::Brotli {
+            quality: 11,
+            lgwin: 16
+        }
+    );
+}
+
+#[test]
+fn every_id_round_trips_through_params() {
+    // zstd-dict needs a dictionary file; see tests/zstd_dict.rs.
+    for &id in compressor_ids().iter().filter(|&&id| id != "zstd-dict") {
+        let defaults = compressor_params(id).unwrap();
+        let spec = CompressorSpec::from_params(id, []).unwrap();
+        assert_eq!(spec.id(), id);
+
+        let params = spec.params();
+        let expected: Vec<_> = defaults.iter().map(|p| (p.name, p.default)).collect();
+        assert_eq!(params, expected, "{id}");
+        assert_eq!(CompressorSpec::from_params(id, params).unwrap(), spec);
+    }
+}
+
+#[test]
+fn from_params_rejects_bad_input() {
+    let err = CompressorSpec::from_params("zip", []).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unknown compressor id: zip (expected one of: gzip, zstd, brotli, lz4, xz, bzip2, ppmd, zstd-dict, gzip-raw, zstd-raw, lz4-raw, xz-raw)"
+    );
+
+    let err = CompressorSpec::from_params("zstd", [("quality", 5)]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unknown parameter for zstd: quality (expected one of: level)"
+    );
+
+    let err = CompressorSpec::from_params("gzip", [("level", -1)]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "gzip level out of range: -1 (expected 0..=9)"
+    );
+}
+
+#[test]
+fn spec_strings_parse_and_round_trip() {
+    assert_eq!(
+        "zstd:level=19".parse::<CompressorSpec>().unwrap(),
+        CompressorSpec::Zstd { level: 19 }
+    );
+    assert_eq!(
+        "gzip".parse::<CompressorSpec>().unwrap(),
+        CompressorSpec::Gzip { level: 9 }
+    );
+
+    let spec: CompressorSpec = "brotli:q=5, lgwin=24".parse().unwrap();
+    assert_eq!(
+        spec,
+        CompressorSpec::Brotli {
+            quality: 5,
+            lgwin: 24
+        }
+    );
+    assert_eq!(spec.to_string(), "brotli:quality=5,lgwin=24");
+
+    for &id in compressor_ids().iter().filter(|&&id| id != "zstd-dict") {
+        let spec: CompressorSpec = id.parse().unwrap();
+        assert_eq!(spec.to_string().parse::<CompressorSpec>().unwrap(), spec);
+        assert_eq!(spec.cache_key(), spec.to_string());
+    }
+}
+
+#[test]
+fn spec_strings_report_what_is_wrong() {
+    assert_eq!(
+        parse_err("brotli:lgwin=25"),
+        "brotli lgwin out of range: 25 (expected 10..=24)"
+    );
+    assert_eq!(
+        parse_err("zstd:level"),
+        "invalid compressor spec `zstd:level`: expected name=value, got `level`"
+    );
+    assert_eq!(
+        parse_err("zstd:level=high"),
+        "zstd level must be an integer, got `high`"
+    );
+    assert_eq!(
+        parse_err("brotli:q=5,quality=6"),
+        "duplicate parameter for brotli: quality"
+    );
+}
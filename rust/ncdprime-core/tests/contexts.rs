@@ -0,0 +1,99 @@
+use ncdprime_core::{
+    CachedCompressor, Compressor, CompressorSpec, Join, SizeCache, compressor_ids, join_bytes,
+};
+
+mod common;
+use common::CountingCompressor;
+
+fn inputs() -> Vec<Vec<u8>> {
+    let mut v: Vec<Vec<u8>> = (0..40)
+        .map(|i| format!("GET /api/v1/items/{} 200 {}ms", i * 13 % 50, i % 7).into_bytes())
+        .collect();
+    v.push(Vec::new());
+    v.push((0..20_000u32).map(|i| (i * 7 % 251) as u8).collect());
+    v.push(b"short".to_vec());
+    v
+}
+
+#[test]
+fn reused_contexts_match_one_shot_sizes() {
+    let inputs = inputs();
+    for id in compressor_ids().iter().filter(|&&id| id != "zstd-dict") {
+        let c = id.parse::<CompressorSpec>().unwrap().build();
+        let mut ctx = c.context();
+        for (x, y) in inputs.iter().zip(inputs.iter().rev()) {
+            assert_eq!(
+                ctx.compressed_len(x).unwrap(),
+                c.compressed_len(x).unwrap(),
+                "{id}"
+            );
+            assert_eq!(
+                ctx.compressed_len_parts(&[x, y]).unwrap(),
+                c.compressed_len(&[&x[..], y].concat()).unwrap(),
+                "{id}"
+            );
+        }
+    }
+}
+
+#[test]
+fn resumed_prefixes_match_full_compression() {
+    let rows: Vec<Vec<u8>> = (0..3u32)
+        .map(|r| {
+            (0..3000u32)
+                .flat_map(|i| format!("{} ", (i * (r + 3)) % 211).into_bytes())
+                .collect()
+        })
+        .collect();
+    let cols = inputs();
+    for id in compressor_ids().iter().filter(|&&id| id != "zstd-dict") {
+        let c = id.parse::<CompressorSpec>().unwrap().build();
+        let mut ctx = c.context();
+        // Revisiting a row after another one, with unrelated calls in between
+        // and a prefix too short to snapshot.
+        for x in [&rows[0], &rows[1], &rows[0], &cols[0], &rows[2]] {
+            for y in &cols {
+                let full = c.compressed_len(&[&x[..], y].concat()).unwrap();
+                assert_eq!(
+                    ctx.compressed_len_resumed(&[&x[..10], &x[10..]], &[y])
+                        .unwrap(),
+                    full,
+                    "{id}: |x|={} |y|={}",
+                    x.len(),
+                    y.len()
+                );
+                ctx.compressed_len(y).unwrap();
+            }
+        }
+        // A prefix that changes on every call, alternating between two rows.
+        for y in &cols {
+            for x in [&rows[1], &rows[2]] {
+                let full = c.compressed_len(&[&x[..], y].concat()).unwrap();
+                assert_eq!(
+                    ctx.compressed_len_resumed(&[x], &[y]).unwrap(),
+                    full,
+                    "{id}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn cached_context_consults_the_cache() {
+    let path = std::env::temp_dir()
+        .join(format!("ncdprime-test-{}", std::process::id()))
+        .join("contexts.bin");
+    let _ = std::fs::remove_file(&path);
+    let cache = SizeCache::open(&path, None).unwrap();
+    let inner = CountingCompressor::default();
+    let spec = CompressorSpec::Gzip { level: 9 };
+    let cached = CachedCompressor::new(&inner, &spec, &cache);
+
+    let mut ctx = cached.context();
+    let joined = join_bytes(b"ab", b"cd", Join::Frame64);
+    assert_eq!(ctx.compressed_len(&joined).unwrap(), 20);
+    assert_eq!(ctx.compressed_len(&joined).unwrap(), 20);
+    assert_eq!(cached.compressed_len(&joined).unwrap(), 20);
+    assert_eq!(inner.calls(), 1);
+}
@@ -0,0 +1,132 @@
+use std::io;
+
+use ncdprime_core::{
+    Compressor, CompressorSpec, Gzip, MatrixOptions, NcdError, NcdOptions, ncd, ncd_matrix,
+    ncd_matrix_rows,
+};
+
+/// Fails on inputs longer than 8 bytes, the way a real compressor's I/O would.
+struct Failing;
+
+impl Compressor for Failing {
+    fn id(&self) -> &'static str {
+        "failing"
+    }
+
+    fn compressed_len(&self, input: &[u8]) -> io::Result<usize> {
+        if input.len() > 8 {
+            return Err(io::Error::other("encoder exploded"));
+        }
+        Ok(input.len())
+    }
+}
+
+/// Rejects large inputs with a typed error passed through `io::Result`.
+struct Limited;
+
+impl Compressor for Limited {
+    fn id(&self) -> &'static str {
+        "limited"
+    }
+
+    fn compressed_len(&self, input: &[u8]) -> io::Result<usize> {
+        if input.len() > 4 {
+            return Err(NcdError::InputTooLarge {
+                id: "limited",
+                len: input.len(),
+                max: 4,
+            }
+            .into());
+        }
+        Ok(input.len())
+    }
+}
+
+#[test]
+fn spec_errors_are_typed() {
+    let err = "zip".parse::<CompressorSpec>().unwrap_err();
+    assert!(matches!(err, NcdError::InvalidSpec(_)), "{err:?}");
+    assert!(err.is_invalid_input());
+
+    match "zstd:level=99".parse::<CompressorSpec>().unwrap_err() {
+        NcdError::OutOfRange {
+            name,
+            value,
+            min,
+            max,
+        } => {
+            assert_eq!(name, "zstd level");
+            assert_eq!((value, min, max), (99, -131072, 22));
+        }
+        err => panic!("expected OutOfRange, got {err:?}"),
+    }
+
+    let err = "zstd-dict:dict=/nonexistent/ncdprime.dict"
+        .parse::<CompressorSpec>()
+        .unwrap_err();
+    assert!(matches!(err, NcdError::Io(_)), "{err:?}");
+    assert!(!err.is_invalid_input());
+}
+
+#[test]
+fn compressor_failures_name_the_compressor() {
+    let long = b"more than eight bytes".to_vec();
+    let err = ncd(&Failing, b"ab", &long, NcdOptions::default()).unwrap_err();
+    let NcdError::Compressor { id, source } = &err else {
+        panic!("expected Compressor, got {err:?}");
+    };
+    assert_eq!(*id, "failing");
+    assert_eq!(source.to_string(), "encoder exploded");
+    assert_eq!(err.to_string(), "failing: encoder exploded");
+
+    let items = [long];
+    let err = ncd_matrix(&Failing, &items, &items, NcdOptions::default()).unwrap_err();
+    assert!(
+        matches!(err, NcdError::Compressor { id: "failing", .. }),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn typed_errors_pass_through_compressors() {
+    let err = ncd(&Limited, b"ab", b"abcdef", NcdOptions::default()).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            NcdError::InputTooLarge {
+                id: "limited",
+                len: 6,
+                max: 4
+            }
+        ),
+        "{err:?}"
+    );
+
+    let e: io::Error = NcdError::Cancelled.into();
+    assert_eq!(e.kind(), io::ErrorKind::Interrupted);
+    assert!(matches!(NcdError::from(e), NcdError::Cancelled));
+}
+
+#[test]
+fn row_sink_can_cancel() {
+    let items: Vec<Vec<u8>> = (0..5).map(|i| format!("item {i}").into_bytes()).collect();
+    let mut rows = 0;
+    let err = ncd_matrix_rows(
+        &Gzip::new(6),
+        &items,
+        &items,
+        NcdOptions::default(),
+        &MatrixOptions::serial(),
+        |_| {},
+        |_, _| {
+            rows += 1;
+            if rows == 2 {
+                return Err(NcdError::Cancelled);
+            }
+            Ok(())
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, NcdError::Cancelled), "{err:?}");
+    assert_eq!(rows, 2);
+}
@@ -0,0 +1,33 @@
+use ncdprime_core::{Compressor, Gzip};
+
+mod common;
+use common::noise;
+
+/// Noise around ~230 KiB of words from a 5000-word vocabulary.
+fn input() -> Vec<u8> {
+    let text: Vec<u8> = (0..40_000u32)
+        .flat_map(|i| format!("w{} ", i.wrapping_mul(2_654_435_761) % 5000).into_bytes())
+        .collect();
+    [noise(3, 150_000), text, noise(5, 150_000)].concat()
+}
+
+#[test]
+fn gzip_sizes_match_flate2() {
+    // What flate2's GzEncoder wrote for this input; cached sizes depend on it.
+    let input = input();
+    assert_eq!(input.len(), 531_139);
+    let (x, y) = input.split_at(200_000);
+    for (level, expected) in [(1, 406_278), (6, 377_537), (9, 377_547)] {
+        let c = Gzip::new(level);
+        assert_eq!(c.compressed_len(&input).unwrap(), expected, "level {level}");
+
+        let mut ctx = c.context();
+        let parts: Vec<&[u8]> = input.chunks(70_001).collect();
+        assert_eq!(ctx.compressed_len_parts(&parts).unwrap(), expected);
+        // The second call resumes from a snapshot of `x`.
+        for _ in 0..2 {
+            let n = ctx.compressed_len_resumed(&[x], &[y]).unwrap();
+            assert_eq!(n, expected, "level {level}");
+        }
+    }
+}
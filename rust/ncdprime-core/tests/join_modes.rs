@@ -0,0 +1,127 @@
+use ncdprime_core::{CompressorSpec, Join, NcdOptions, Separator, join_bytes, ncd};
+
+mod common;
+use common::{noise, prose};
+
+fn opts(join: Join) -> NcdOptions {
+    NcdOptions {
+        join,
+        ..NcdOptions::default()
+    }
+}
+
+#[test]
+fn names_round_trip() {
+    for s in ["frame64", "concat", "sep:00", "sep:0d0a", "interleave:1024"] {
+        let join: Join = s.parse().unwrap();
+        assert_eq!(join.to_string(), s);
+    }
+    assert_eq!(
+        "sep".parse::<Join>().unwrap(),
+        Join::Separator(Separator::NUL)
+    );
+    assert_eq!(
+        "interleave".parse::<Join>().unwrap(),
+        Join::Interleave {
+            chunk: Join::DEFAULT_CHUNK
+        }
+    );
+    assert_eq!(
+        "sep:FF".parse::<Join>().unwrap().to_string(),
+        "sep:ff",
+        "hex is printed lowercase"
+    );
+}
+
+#[test]
+fn bad_parameters_are_rejected() {
+    for (s, msg) in [
+        (
+            "interleave:0",
+            "join interleave chunk out of range: 0 (expected 1..=16777216)",
+        ),
+        (
+            "interleave:x",
+            "join interleave chunk must be an integer, got `x`",
+        ),
+        ("sep:", "join separator must be 1 to 16 bytes, got 0"),
+        (
+            "sep:0",
+            "join separator must be hex bytes, e.g. sep:0a, got \"0\"",
+        ),
+        (
+            "sep:zz",
+            "join separator must be hex bytes, e.g. sep:0a, got \"zz\"",
+        ),
+        ("concat:1", "join concat takes no parameter: concat:1"),
+    ] {
+        let err = s.parse::<Join>().unwrap_err();
+        assert!(err.is_invalid_input(), "{s}: {err:?}");
+        assert_eq!(err.to_string(), msg);
+    }
+    assert!(Separator::new(&[7; 17]).is_err());
+}
+
+#[test]
+fn joined_bytes() {
+    let (x, y) = (b"abcde".as_slice(), b"XY".as_slice());
+    assert_eq!(join_bytes(x, y, Join::Concat), b"abcdeXY");
+    assert_eq!(join_bytes(x, y, "sep:2c20".parse().unwrap()), b"abcde, XY");
+    assert_eq!(join_bytes(x, y, Join::Interleave { chunk: 2 }), b"abXYcde");
+    assert_eq!(join_bytes(b"", y, Join::Interleave { chunk: 2 }), b"XY");
+}
+
+#[test]
+fn identity_distance_is_small_for_every_join() {
+    let x = noise(5, 4000);
+    for spec in ["gzip", "xz", "bzip2", "ppmd"] {
+        let c = spec.parse::<CompressorSpec>().unwrap().build();
+        for &join in Join::ALL {
+            let d = ncd(&*c, &x, &x, opts(join)).unwrap();
+            assert!(d < 0.2, "{spec} {join}: NCD(x, x) = {d}");
+        }
+    }
+}
+
+#[test]
+fn interleave_keeps_large_inputs_inside_the_gzip_window() {
+    // 64 KiB halves: with x || y the copy of x is beyond gzip's 32 KiB window.
+    let x = noise(7, 64 * 1024);
+    let c = CompressorSpec::Gzip { level: 9 }.build();
+    let concat = ncd(&*c, &x, &x, opts(Join::Concat)).unwrap();
+    let interleave = ncd(&*c, &x, &x, opts("interleave:4096".parse().unwrap())).unwrap();
+    assert!(concat > 0.9, "concat NCD(x, x) = {concat}");
+    assert!(interleave < 0.1, "interleave NCD(x, x) = {interleave}");
+}
+
+#[test]
+fn triangle_inequality_holds_on_a_sample() {
+    let items = [
+        prose(0, 800),
+        prose(1, 800),
+        prose(5, 800),
+        [prose(0, 400), noise(3, 2000)].concat(),
+        noise(3, 4000),
+        noise(9, 4000),
+    ];
+    let c = CompressorSpec::Gzip { level: 9 }.build();
+    for &join in Join::ALL {
+        let o = opts(join);
+        let d: Vec<Vec<f64>> = items
+            .iter()
+            .map(|a| items.iter().map(|b| ncd(&*c, a, b, o).unwrap()).collect())
+            .collect();
+        for i in 0..items.len() {
+            for j in 0..items.len() {
+                for k in 0..items.len() {
+                    assert!(
+                        d[i][k] <= d[i][j] + d[j][k] + 1e-9,
+                        "{join}: d({i},{k}) = {} > d({i},{j}) + d({j},{k}) = {}",
+                        d[i][k],
+                        d[i][j] + d[j][k]
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use ncdprime_core::{CompressorSpec, Lz4};
+
+fn corpus() -> Vec<u8> {
+    (0..4000u32)
+        .flat_map(|i| {
+            format!(
+                "line {} value={} {}\n",
+                i % 97,
+                (i * 31) % 1000,
+                ["alpha", "beta", "gamma"][(i % 3) as usize]
+            )
+            .into_bytes()
+        })
+        .collect()
+}
+
+fn size(spec: &str) -> usize {
+    let c = spec.parse::<CompressorSpec>().unwrap().build();
+    c.compressed_len(&corpus()).unwrap()
+}
+
+#[test]
+fn acceleration_trades_ratio_for_speed() {
+    let sizes: Vec<usize> = [1, 4, 64, 1000]
+        .iter()
+        .map(|a| size(&format!("lz4:accel={a}")))
+        .collect();
+    assert!(sizes.windows(2).all(|w| w[0] <= w[1]), "{sizes:?}");
+    assert!(
+        sizes[0] < sizes[3],
+        "accel must change the output: {sizes:?}"
+    );
+}
+
+#[test]
+fn hc_levels_beat_fast_mode() {
+    let fast = size("lz4");
+    let hc: Vec<usize> = [1, 9, 12]
+        .iter()
+        .map(|l| size(&format!("lz4:hc={l}")))
+        .collect();
+    assert!(hc.windows(2).all(|w| w[0] >= w[1]), "{hc:?}");
+    assert!(hc[2] < fast, "hc=12 {} vs fast {fast}", hc[2]);
+
+    use ncdprime_core::Compressor;
+    assert_eq!(
+        Lz4::new(1).with_hc(12).compressed_len(&corpus()).unwrap(),
+        hc[2]
+    );
+}
+
+#[test]
+fn hc_specs_round_trip_and_validate() {
+    let spec: CompressorSpec = "lz4:hc=9".parse().unwrap();
+    assert_eq!(spec, CompressorSpec::Lz4 { accel: 1, hc: 9 });
+    assert_eq!(spec.to_string(), "lz4:accel=1,hc=9");
+    assert_eq!(
+        "lz4-raw:hc=3".parse::<CompressorSpec>().unwrap(),
+        CompressorSpec::Lz4Raw { accel: 1, hc: 3 }
+    );
+
+    let err = "lz4:accel=8,hc=9".parse::<CompressorSpec>().unwrap_err();
+    assert!(err.to_string().contains("mutually exclusive"), "{err}");
+    let err = "lz4:hc=13".parse::<CompressorSpec>().unwrap_err();
+    assert_eq!(err.to_string(), "lz4 hc out of range: 13 (expected 0..=12)");
+}
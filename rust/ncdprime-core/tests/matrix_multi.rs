@@ -0,0 +1,144 @@
+use ncdprime_core::{
+    CompressorSpec, MatrixOptions, NcdOptions, SizeCache, ncd_matrix, ncd_matrix_multi,
+    ncd_matrix_multi_rows,
+};
+
+fn items() -> Vec<Vec<u8>> {
+    (0..5u8)
+        .map(|k| {
+            (0..400u32)
+                .map(|i| ((i * (k as u32 + 3)) % 29) as u8 + b'a')
+                .collect()
+        })
+        .collect()
+}
+
+fn specs() -> Vec<CompressorSpec> {
+    ["gzip:level=6", "zstd:level=3", "lz4"]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect()
+}
+
+#[test]
+fn one_matrix_per_compressor_matches_single_runs() {
+    let a = items();
+    let b: Vec<Vec<u8>> = a.iter().rev().take(3).cloned().collect();
+    let opts = NcdOptions::default();
+
+    for (a, b) in [(&a, &b), (&a, &a)] {
+        let runs = ncd_matrix_multi(
+            &specs(),
+            a,
+            b,
+            opts,
+            &MatrixOptions::default(),
+            None,
+            |_, _| {},
+        )
+        .unwrap();
+        assert_eq!(runs.len(), 3);
+        for (run, spec) in runs.iter().zip(specs()) {
+            assert_eq!(run.spec, spec);
+            let expected = ncd_matrix(&*spec.build(), a, b, opts).unwrap();
+            assert_eq!(run.matrix, expected, "{spec}");
+        }
+    }
+}
+
+#[test]
+fn streamed_rows_arrive_per_compressor_in_order() {
+    let a = items();
+    let opts = NcdOptions::default();
+
+    let mut seen: Vec<(usize, usize)> = Vec::new();
+    let mut matrices = vec![Vec::new(); 3];
+    let elapsed = ncd_matrix_multi_rows(
+        &specs(),
+        &a,
+        &a,
+        opts,
+        &MatrixOptions::default(),
+        None,
+        |_, _| {},
+        |k, i, row| {
+            seen.push((k, i));
+            matrices[k].push(row);
+            Ok(())
+        },
+    )
+    .unwrap();
+
+    assert_eq!(elapsed.len(), 3);
+    let expected: Vec<_> = (0..3)
+        .flat_map(|k| (0..a.len()).map(move |i| (k, i)))
+        .collect();
+    assert_eq!(seen, expected);
+    for (matrix, spec) in matrices.iter().zip(specs()) {
+        assert_eq!(
+            *matrix,
+            ncd_matrix(&*spec.build(), &a, &a, opts).unwrap(),
+            "{spec}"
+        );
+    }
+}
+
+#[test]
+fn progress_is_tagged_with_the_compressor_index() {
+    let a = items();
+    let mut seen = vec![0usize; 3];
+    ncd_matrix_multi(
+        &specs(),
+        &a,
+        &a[..2],
+        NcdOptions::default(),
+        &MatrixOptions::serial(),
+        None,
+        |k, p| {
+            seen[k] += 1;
+            assert_eq!(p.done, seen[k]);
+        },
+    )
+    .unwrap();
+    assert_eq!(seen, vec![10, 10, 10]);
+}
+
+#[test]
+fn sizes_are_recorded_in_the_cache_per_compressor() {
+    let path = std::env::temp_dir()
+        .join(format!("ncdprime-test-{}", std::process::id()))
+        .join("multi.bin");
+    let _ = std::fs::remove_file(&path);
+    let cache = SizeCache::open(&path, None).unwrap();
+
+    let a = items();
+    let opts = NcdOptions::default();
+    let first = ncd_matrix_multi(
+        &specs(),
+        &a,
+        &a,
+        opts,
+        &MatrixOptions::default(),
+        Some(&cache),
+        |_, _| {},
+    )
+    .unwrap();
+    let entries = cache.stats().entries;
+    assert!(entries > 0);
+
+    let second = ncd_matrix_multi(
+        &specs(),
+        &a,
+        &a,
+        opts,
+        &MatrixOptions::default(),
+        Some(&cache),
+        |_, _| {},
+    )
+    .unwrap();
+    assert_eq!(cache.stats().entries, entries);
+    for (x, y) in first.iter().zip(&second) {
+        assert_eq!(x.matrix, y.matrix);
+    }
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,121 @@
+use ncdprime_core::{
+    Compressor, Gzip, MatrixOptions, NcdOptions, Zstd, ncd_matrix, ncd_matrix_parallel,
+    ncd_matrix_parallel_with_progress,
+};
+
+mod common;
+use common::CountingCompressor;
+
+fn corpus() -> Vec<Vec<u8>> {
+    (0..9)
+        .map(|i| {
+            format!("item {i}: the quick brown fox jumps over the lazy dog\n")
+                .repeat(i + 1)
+                .into_bytes()
+        })
+        .collect()
+}
+
+#[test]
+fn parallel_matches_serial_bit_for_bit() {
+    let a = corpus();
+    let b: Vec<Vec<u8>> = corpus().into_iter().rev().take(5).collect();
+
+    for c in [
+        Box::new(Gzip::new(6)) as Box<dyn Compressor>,
+        Box::new(Zstd::new(3)),
+    ] {
+        let serial = ncd_matrix(&*c, &a, &b, NcdOptions::default()).unwrap();
+
+        for threads in [0, 2, 3, 8, 64] {
+            let par = ncd_matrix_parallel(
+                &*c,
+                &a,
+                &b,
+                NcdOptions::default(),
+                &MatrixOptions { threads },
+            )
+            .unwrap();
+            assert_eq!(serial.len(), par.len());
+            for (rs, rp) in serial.iter().zip(&par) {
+                let rs: Vec<u64> = rs.iter().map(|v| v.to_bits()).collect();
+                let rp: Vec<u64> = rp.iter().map(|v| v.to_bits()).collect();
+                assert_eq!(rs, rp, "{} threads={threads}", c.id());
+            }
+        }
+    }
+}
+
+#[test]
+fn parallel_progress_runs_on_caller_with_monotone_done() {
+    let c = Gzip::new(6);
+    let a = corpus();
+    let b: Vec<Vec<u8>> = corpus().into_iter().rev().collect();
+    let caller = std::thread::current().id();
+
+    let mut seen = Vec::new();
+    let m = ncd_matrix_parallel_with_progress(
+        &c,
+        &a,
+        &b,
+        NcdOptions::default(),
+        &MatrixOptions { threads: 4 },
+        |p| {
+            assert_eq!(std::thread::current().id(), caller);
+            assert_eq!(p.total, a.len() * b.len());
+            seen.push(p.done);
+        },
+    )
+    .unwrap();
+
+    assert_eq!(m.len(), a.len());
+    assert_eq!(seen, (1..=a.len() * b.len()).collect::<Vec<_>>());
+}
+
+#[test]
+fn parallel_keeps_singleton_dedup() {
+    let c = CountingCompressor::default();
+    let a = vec![b"aaa".to_vec(), b"bbb".to_vec(), b"aaa".to_vec()];
+    let b = vec![b"aaa".to_vec(), b"aaa".to_vec()];
+
+    ncd_matrix_parallel(
+        &c,
+        &a,
+        &b,
+        NcdOptions::default(),
+        &MatrixOptions { threads: 3 },
+    )
+    .unwrap();
+
+    // 2 unique singletons + 2 joins per cell (symmetry=min)
+    assert_eq!(c.calls(), 2 + 2 * a.len() * b.len());
+}
+
+struct FailingCompressor;
+
+impl Compressor for FailingCompressor {
+    fn id(&self) -> &'static str {
+        "fail"
+    }
+
+    fn compressed_len(&self, input: &[u8]) -> std::io::Result<usize> {
+        if input.len() > 8 {
+            return Err(std::io::Error::other("boom"));
+        }
+        Ok(input.len())
+    }
+}
+
+#[test]
+fn parallel_propagates_errors() {
+    let a = vec![b"a".to_vec(); 16];
+    let err = ncd_matrix_parallel(
+        &FailingCompressor,
+        &a,
+        &a,
+        NcdOptions::default(),
+        &MatrixOptions { threads: 4 },
+    )
+    .unwrap_err();
+    assert_eq!(err.to_string(), "fail: boom");
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+
+use ncdprime_core::{
+    Gzip, MatrixOptions, NcdOptions, ncd_matrix, ncd_matrix_rows, ncd_matrix_rows_resume,
+    ncd_matrix_square,
+};
+
+mod common;
+use common::CountingCompressor;
+
+fn corpus(n: usize) -> Vec<Vec<u8>> {
+    (0..n)
+        .map(|i| format!("row {i} {}\n", "ab".repeat(i * 7)).into_bytes())
+        .collect()
+}
+
+fn collect_rows(a: &[Vec<u8>], b: &[Vec<u8>], threads: usize) -> Vec<Vec<f64>> {
+    let mut out = Vec::new();
+    ncd_matrix_rows(
+        &Gzip::new(6),
+        a,
+        b,
+        NcdOptions::default(),
+        &MatrixOptions { threads },
+        |_| {},
+        |i, row| {
+            assert_eq!(i, out.len(), "rows must arrive in order");
+            out.push(row);
+            Ok(())
+        },
+    )
+    .unwrap();
+    out
+}
+
+#[test]
+fn streamed_rows_match_materialized_matrix() {
+    let a = corpus(7);
+    let b = corpus(3);
+    let expected = ncd_matrix(&Gzip::new(6), &a, &b, NcdOptions::default()).unwrap();
+
+    for threads in [1, 2, 5] {
+        assert_eq!(collect_rows(&a, &b, threads), expected);
+    }
+}
+
+#[test]
+fn streamed_square_rows_match_square_matrix() {
+    let xs = corpus(9);
+    let expected = ncd_matrix_square(
+        &Gzip::new(6),
+        &xs,
+        NcdOptions::default(),
+        &MatrixOptions::serial(),
+    )
+    .unwrap();
+
+    for threads in [1, 4] {
+        assert_eq!(collect_rows(&xs, &xs, threads), expected);
+    }
+}
+
+#[test]
+fn row_sink_error_stops_the_matrix() {
+    let a = corpus(6);
+    let mut rows = 0;
+    let mut cells = 0;
+    let err = ncd_matrix_rows(
+        &Gzip::new(6),
+        &a,
+        &corpus(2),
+        NcdOptions::default(),
+        &MatrixOptions::serial(),
+        |_| cells += 1,
+        |_, _| {
+            rows += 1;
+            Err(std::io::Error::other("closed pipe").into())
+        },
+    )
+    .unwrap_err();
+
+    assert_eq!(err.to_string(), "closed pipe");
+    assert_eq!(rows, 1);
+    assert_eq!(cells, 2);
+}
+
+#[test]
+fn resume_skips_known_cells() {
+    use std::collections::HashMap;
+
+    let a = corpus(4);
+    let b = corpus(3);
+    let c = Gzip::new(6);
+    let expected = ncd_matrix(&c, &a, &b, NcdOptions::default()).unwrap();
+
+    let known: HashMap<(usize, usize), f64> = [(0, 0), (1, 2), (3, 1)]
+        .into_iter()
+        .map(|(i, j)| ((i, j), expected[i][j]))
+        .collect();
+
+    let mut computed = Vec::new();
+    let mut out = Vec::new();
+    ncdprime_core::ncd_matrix_rows_resume(
+        &c,
+        &a,
+        &b,
+        NcdOptions::default(),
+        &MatrixOptions { threads: 2 },
+        &known,
+        |p| {
+            assert_eq!(p.total, 4 * 3 - known.len());
+            assert_eq!(p.value, expected[p.row][p.col]);
+            computed.push((p.row, p.col));
+        },
+        |_, row| {
+            out.push(row);
+            Ok(())
+        },
+    )
+    .unwrap();
+
+    assert_eq!(out, expected);
+    assert_eq!(computed.len(), 4 * 3 - known.len());
+    assert!(computed.iter().all(|cell| !known.contains_key(cell)));
+}
+
+#[test]
+fn streamed_square_rows_mirror_hundreds_of_items() {
+    use ncdprime_core::Lz4;
+
+    let xs: Vec<Vec<u8>> = (0..300)
+        .map(|i| format!("item {i} {}", "xy".repeat(i % 17)).into_bytes())
+        .collect();
+    let n = xs.len();
+    let c = Lz4::new(1);
+    let expected = ncd_matrix_square(
+        &c,
+        &xs,
+        NcdOptions::default(),
+        &MatrixOptions { threads: 4 },
+    )
+    .unwrap();
+
+    let mut out = Vec::new();
+    let (mut done, mut total) = (0, 0);
+    ncd_matrix_rows(
+        &c,
+        &xs,
+        &xs,
+        NcdOptions::default(),
+        &MatrixOptions { threads: 4 },
+        |p| (done, total) = (p.done, p.total),
+        |_, row| {
+            out.push(row);
+            Ok(())
+        },
+    )
+    .unwrap();
+
+    assert_eq!(out, expected);
+    assert_eq!((done, total), (n * (n + 1) / 2, n * (n + 1) / 2));
+
+    // The path `matrix --square` takes: each pair is compressed once.
+    let c = CountingCompressor::default();
+    ncd_matrix_rows_resume(
+        &c,
+        &xs,
+        &xs,
+        NcdOptions::default(),
+        &MatrixOptions { threads: 4 },
+        &HashMap::new(),
+        |_| {},
+        |_, _| Ok(()),
+    )
+    .unwrap();
+    // n singletons, one C(xx) per diagonal cell and C(xy), C(yx) above it.
+    assert_eq!(c.calls(), n + n + 2 * n * (n - 1) / 2);
+}
+
+#[test]
+fn streamed_square_resume_counts_mirrored_known_cells() {
+    let xs = corpus(5);
+    let c = Gzip::new(6);
+    let expected =
+        ncd_matrix_square(&c, &xs, NcdOptions::default(), &MatrixOptions::serial()).unwrap();
+    // (3, 1) stands for (1, 3) as well.
+    let known: HashMap<(usize, usize), f64> = [(0, 2), (3, 1), (4, 4)]
+        .into_iter()
+        .map(|(i, j)| ((i, j), expected[i][j]))
+        .collect();
+
+    let mut computed = Vec::new();
+    let mut out = Vec::new();
+    ncd_matrix_rows_resume(
+        &c,
+        &xs,
+        &xs,
+        NcdOptions::default(),
+        &MatrixOptions::serial(),
+        &known,
+        |p| {
+            assert_eq!(p.total, 5 * 6 / 2 - known.len());
+            computed.push((p.row.min(p.col), p.row.max(p.col)));
+        },
+        |_, row| {
+            out.push(row);
+            Ok(())
+        },
+    )
+    .unwrap();
+
+    assert_eq!(out, expected);
+    assert_eq!(computed.len(), 5 * 6 / 2 - known.len());
+    assert!(!computed.contains(&(1, 3)));
+}
+
+#[test]
+fn streamed_square_rows_past_the_mirror_budget() {
+    // Past 2048 items the streamed matrix mirrors within blocks of rows only.
+    let xs: Vec<Vec<u8>> = (0..2100u32).map(|i| i.to_le_bytes().to_vec()).collect();
+    let n = xs.len();
+    let c = CountingCompressor::default();
+    let (mut done, mut total) = (0, 0);
+    let mut rows = Vec::new();
+    ncd_matrix_rows(
+        &c,
+        &xs,
+        &xs,
+        NcdOptions::default(),
+        &MatrixOptions { threads: 4 },
+        |p| (done, total) = (p.done, p.total),
+        |_, row| {
+            rows.push(row);
+            Ok(())
+        },
+    )
+    .unwrap();
+
+    assert_eq!(done, total);
+    assert!(total > n * (n + 1) / 2 && total < n * n, "{total}");
+    assert_eq!(c.calls(), n + n + 2 * (total - n));
+    for (i, row) in rows.iter().enumerate().step_by(97) {
+        for (j, &d) in row.iter().enumerate().step_by(89) {
+            assert_eq!(d.to_bits(), rows[j][i].to_bits(), "({i}, {j})");
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use ncdprime_core::{
+    Gzip, MatrixOptions, NcdOptions, Symmetry, ncd, ncd_matrix, ncd_matrix_square,
+    ncd_matrix_square_with_progress,
+};
+
+mod common;
+use common::CountingCompressor;
+
+fn items() -> Vec<Vec<u8>> {
+    vec![
+        b"aaaa".to_vec(),
+        b"abab".to_vec(),
+        b"hello hello".to_vec(),
+        b"xyz".to_vec(),
+    ]
+}
+
+#[test]
+fn square_matches_scalar_and_is_symmetric() {
+    let c = Gzip::new(6);
+    let xs = items();
+
+    for threads in [1, 3] {
+        let m =
+            ncd_matrix_square(&c, &xs, NcdOptions::default(), &MatrixOptions { threads }).unwrap();
+        for i in 0..xs.len() {
+            for j in 0..xs.len() {
+                let d = ncd(&c, &xs[i], &xs[j], NcdOptions::default()).unwrap();
+                assert_eq!(m[i][j].to_bits(), d.to_bits(), "cell ({i}, {j})");
+                assert_eq!(m[i][j].to_bits(), m[j][i].to_bits());
+            }
+        }
+    }
+}
+
+#[test]
+fn square_compresses_upper_triangle_only() {
+    let c = CountingCompressor::default();
+    let xs = items();
+    let n = xs.len();
+
+    let mut progress = 0;
+    ncd_matrix_square_with_progress(
+        &c,
+        &xs,
+        NcdOptions::default(),
+        &MatrixOptions::serial(),
+        |p| {
+            assert_eq!(p.total, n * (n + 1) / 2);
+            progress = p.done;
+        },
+    )
+    .unwrap();
+
+    assert_eq!(progress, n * (n + 1) / 2);
+    // singletons + one self-join per diagonal cell + two joins per off-diagonal pair
+    assert_eq!(c.calls(), n + n + 2 * n * (n - 1) / 2);
+}
+
+#[test]
+fn matrix_detects_identical_sets() {
+    let c = CountingCompressor::default();
+    let a = items();
+    let b = items();
+    let n = a.len();
+
+    ncd_matrix(&c, &a, &b, NcdOptions::default()).unwrap();
+    assert_eq!(c.calls(), n + n + 2 * n * (n - 1) / 2);
+}
+
+#[test]
+fn square_without_symmetry_computes_every_cell() {
+    let c = CountingCompressor::default();
+    let xs = items();
+    let n = xs.len();
+    let opts = NcdOptions {
+        symmetry: Symmetry::None,
+        ..Default::default()
+    };
+
+    ncd_matrix_square(&c, &xs, opts, &MatrixOptions::serial()).unwrap();
+    assert_eq!(c.calls(), n + n * n);
+}
@@ -0,0 +1,87 @@
+use ncdprime_core::{
+    CompressorSpec, Gzip, MatrixOptions, Metric, NcdOptions, Symmetry, ncd, ncd_matrix,
+    ncd_matrix_square,
+};
+
+mod common;
+use common::noise;
+
+fn items() -> Vec<Vec<u8>> {
+    let a = noise(1, 3000);
+    vec![
+        a.clone(),
+        [&a[..1500], &noise(2, 1500)[..]].concat(),
+        b"the quick brown fox jumps over the lazy dog ".repeat(40),
+        noise(3, 800),
+    ]
+}
+
+fn opts(symmetry: Symmetry, metric: Metric) -> NcdOptions {
+    NcdOptions {
+        symmetry,
+        metric,
+        ..NcdOptions::default()
+    }
+}
+
+#[test]
+fn formulas() {
+    let (cx, cy, cxy) = (100.0, 80.0, 120.0);
+    assert_eq!(Metric::Ncd.distance(cx, cy, cxy), 0.4);
+    assert_eq!(Metric::Cdm.distance(cx, cy, cxy), 120.0 / 180.0);
+    assert_eq!(Metric::Clm.distance(cx, cy, cxy), 1.0 - 60.0 / 120.0);
+    for m in Metric::ALL {
+        assert_eq!(m.distance(0.0, 0.0, 0.0), 0.0, "{m}");
+    }
+
+    assert_eq!(Symmetry::None.combine(7.0, 5.0), 7.0);
+    assert_eq!(Symmetry::Min.combine(7.0, 5.0), 5.0);
+    assert_eq!(Symmetry::Avg.combine(7.0, 5.0), 6.0);
+    assert_eq!(Symmetry::Max.combine(7.0, 5.0), 7.0);
+}
+
+#[test]
+fn metrics_separate_identical_from_unrelated() {
+    let (x, y) = (noise(1, 4000), noise(2, 4000));
+    let c = CompressorSpec::Xz { level: 6 }.build();
+    for (metric, same_below, far_above) in [
+        (Metric::Ncd, 0.1, 0.9),
+        (Metric::Cdm, 0.55, 0.95),
+        (Metric::Clm, 0.1, 0.9),
+    ] {
+        let o = opts(Symmetry::Min, metric);
+        let same = ncd(&*c, &x, &x, o).unwrap();
+        let far = ncd(&*c, &x, &y, o).unwrap();
+        assert!(same < same_below, "{metric}: identical {same}");
+        assert!(far > far_above, "{metric}: unrelated {far}");
+    }
+}
+
+#[test]
+fn avg_lies_between_min_and_max() {
+    let xs = items();
+    let c = Gzip::new(9);
+    for metric in Metric::ALL.iter().copied() {
+        for x in &xs {
+            for y in &xs {
+                let [min, avg, max] = [Symmetry::Min, Symmetry::Avg, Symmetry::Max]
+                    .map(|s| ncd(&c, x, y, opts(s, metric)).unwrap());
+                assert!(min <= avg && avg <= max, "{metric}: {min} {avg} {max}");
+            }
+        }
+    }
+}
+
+#[test]
+fn symmetric_modes_mirror_square_matrices() {
+    let xs = items();
+    let c = Gzip::new(9);
+    for &symmetry in &[Symmetry::Avg, Symmetry::Max] {
+        for &metric in Metric::ALL {
+            let o = opts(symmetry, metric);
+            let full = ncd_matrix(&c, &xs, &xs, o).unwrap();
+            let square = ncd_matrix_square(&c, &xs, o, &MatrixOptions::serial()).unwrap();
+            assert_eq!(full, square, "{symmetry} {metric}");
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use ncdprime_core::{
+    Compressor, Gzip, Join, JoinDirection, MatrixOptions, NcdOptions, Symmetry, ncd, ncd_detail,
+    ncd_matrix_parallel_with_progress,
+};
+
+/// Size = length + first byte, so C(xy) and C(yx) differ by x[0] - y[0].
+struct FirstByte;
+
+impl Compressor for FirstByte {
+    fn id(&self) -> &'static str {
+        "first-byte"
+    }
+
+    fn compressed_len(&self, input: &[u8]) -> std::io::Result<usize> {
+        Ok(input.len() + input.first().map_or(0, |&b| usize::from(b)))
+    }
+}
+
+fn opts(symmetry: Symmetry) -> NcdOptions {
+    NcdOptions {
+        join: Join::Concat,
+        symmetry,
+        ..NcdOptions::default()
+    }
+}
+
+#[test]
+fn sizes_and_direction() {
+    let (x, y) = (b"\x0aaaa".as_slice(), b"\x14bbbb".as_slice());
+
+    let d = ncd_detail(&FirstByte, x, y, opts(Symmetry::None)).unwrap();
+    assert_eq!((d.cx, d.cy, d.cxy, d.cyx), (14, 25, 19, None));
+    assert_eq!(d.direction, JoinDirection::Xy);
+
+    for (symmetry, direction) in [
+        (Symmetry::Min, JoinDirection::Xy),
+        (Symmetry::Max, JoinDirection::Yx),
+        (Symmetry::Avg, JoinDirection::Both),
+    ] {
+        let d = ncd_detail(&FirstByte, x, y, opts(symmetry)).unwrap();
+        assert_eq!((d.cxy, d.cyx), (19, Some(29)), "{symmetry}");
+        assert_eq!(d.direction, direction, "{symmetry}");
+
+        let d = ncd_detail(&FirstByte, y, x, opts(symmetry)).unwrap();
+        assert_eq!((d.cxy, d.cyx), (29, Some(19)), "{symmetry}");
+    }
+
+    let d = ncd_detail(&FirstByte, y, x, opts(Symmetry::Min)).unwrap();
+    assert_eq!(d.direction, JoinDirection::Yx);
+}
+
+#[test]
+fn self_distance_compresses_one_join() {
+    let x = b"abcabcabc".to_vec();
+    let d = ncd_detail(&Gzip::new(9), &x, &x, NcdOptions::default()).unwrap();
+    assert_eq!(d.cx, d.cy);
+    assert_eq!(d.cyx, None);
+}
+
+#[test]
+fn detail_matches_plain_distance() {
+    let c = Gzip::new(9);
+    let x = b"the quick brown fox jumps over the lazy dog".repeat(3);
+    let y = b"the quick brown cat naps under the lazy dog".repeat(3);
+    for &symmetry in Symmetry::ALL {
+        let o = NcdOptions {
+            symmetry,
+            ..NcdOptions::default()
+        };
+        let d = ncd_detail(&c, &x, &y, o).unwrap();
+        assert_eq!(d.distance.to_bits(), ncd(&c, &x, &y, o).unwrap().to_bits());
+        assert_eq!(d.cx, c.compressed_len(&x).unwrap());
+    }
+}
+
+#[test]
+fn matrix_progress_carries_the_cell_sizes() {
+    let c = Gzip::new(6);
+    let a = vec![b"aaaa".to_vec(), b"hello hello".to_vec()];
+    let b = vec![b"abab".to_vec(), b"xyz".to_vec(), b"hello".to_vec()];
+    let o = NcdOptions::default();
+
+    let mut seen = 0;
+    ncd_matrix_parallel_with_progress(&c, &a, &b, o, &MatrixOptions { threads: 2 }, |p| {
+        let mut want = ncd_detail(&c, &a[p.row], &b[p.col], o).unwrap();
+        want.wall = p.detail.wall;
+        assert_eq!(p.detail, want, "cell ({}, {})", p.row, p.col);
+        assert_eq!(p.value.to_bits(), p.detail.distance.to_bits());
+        seen += 1;
+    })
+    .unwrap();
+    assert_eq!(seen, a.len() * b.len());
+}
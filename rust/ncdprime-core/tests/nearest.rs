@@ -0,0 +1,80 @@
+use ncdprime_core::{
+    Compressor, Gzip, MatrixOptions, NcdError, NcdOptions, ncd_matrix, ncd_nearest,
+};
+
+/// Every input "compresses" to 1 byte, so every distance is equal.
+struct Constant;
+
+impl Compressor for Constant {
+    fn id(&self) -> &'static str {
+        "constant"
+    }
+
+    fn compressed_len(&self, _input: &[u8]) -> std::io::Result<usize> {
+        Ok(1)
+    }
+}
+
+fn items(words: &[&str]) -> Vec<Vec<u8>> {
+    words.iter().map(|w| w.repeat(6).into_bytes()).collect()
+}
+
+#[test]
+fn matches_the_sorted_matrix_row() {
+    let c = Gzip::new(9);
+    let queries = items(&["apple pie ", "zebra crossing "]);
+    let refs = items(&[
+        "zebra stripes ",
+        "apple tart ",
+        "banana bread ",
+        "apple pie! ",
+        "zebra crossing ",
+    ]);
+    let opts = NcdOptions::default();
+    let matrix = ncd_matrix(&c, &queries, &refs, opts).unwrap();
+
+    let nearest = ncd_nearest(&c, &queries, &refs, 3, opts, &MatrixOptions { threads: 3 }).unwrap();
+    assert_eq!(nearest.len(), queries.len());
+    for (row, neighbors) in matrix.iter().zip(&nearest) {
+        let mut want: Vec<usize> = (0..refs.len()).collect();
+        want.sort_by(|&a, &b| row[a].total_cmp(&row[b]).then(a.cmp(&b)));
+        let got: Vec<usize> = neighbors.iter().map(|n| n.index).collect();
+        assert_eq!(got, want[..3]);
+        for n in neighbors {
+            assert_eq!(n.distance.to_bits(), row[n.index].to_bits());
+        }
+    }
+    assert_eq!(nearest[0][0].index, 3);
+    assert_eq!(nearest[1][0].index, 4);
+}
+
+#[test]
+fn ties_keep_reference_order() {
+    let queries = items(&["q"]);
+    let refs = items(&["a", "b", "c", "d"]);
+    let opts = NcdOptions::default();
+    let nearest = ncd_nearest(
+        &Constant,
+        &queries,
+        &refs,
+        2,
+        opts,
+        &MatrixOptions::serial(),
+    )
+    .unwrap();
+    let got: Vec<usize> = nearest[0].iter().map(|n| n.index).collect();
+    assert_eq!(got, [0, 1]);
+}
+
+#[test]
+fn k_is_capped_by_the_reference_count() {
+    let c = Gzip::new(6);
+    let queries = items(&["one ", "two "]);
+    let refs = items(&["three ", "four "]);
+    let opts = NcdOptions::default();
+    let nearest = ncd_nearest(&c, &queries, &refs, 10, opts, &MatrixOptions::serial()).unwrap();
+    assert!(nearest.iter().all(|n| n.len() == 2));
+
+    let err = ncd_nearest(&c, &queries, &refs, 0, opts, &MatrixOptions::serial()).unwrap_err();
+    assert!(matches!(err, NcdError::InvalidSpec(_)), "{err:?}");
+}
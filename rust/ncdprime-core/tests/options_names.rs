@@ -0,0 +1,70 @@
+use ncdprime_core::{
+    Join, Metric, NcdError, NcdOptions, Symmetry, Threshold, TieBreak, TreeMethod, Vote,
+};
+
+#[test]
+fn names_round_trip() {
+    for j in Join::ALL {
+        assert_eq!(j.name().parse::<Join>().unwrap(), *j);
+    }
+    for s in Symmetry::ALL {
+        assert_eq!(s.to_string().parse::<Symmetry>().unwrap(), *s);
+    }
+    for m in Metric::ALL {
+        assert_eq!(m.to_string().parse::<Metric>().unwrap(), *m);
+    }
+    for t in Threshold::ALL {
+        assert_eq!(t.to_string().parse::<Threshold>().unwrap(), *t);
+    }
+    for v in Vote::ALL {
+        assert_eq!(v.to_string().parse::<Vote>().unwrap(), *v);
+    }
+    for t in TieBreak::ALL {
+        assert_eq!(t.to_string().parse::<TieBreak>().unwrap(), *t);
+    }
+    for m in TreeMethod::ALL {
+        assert_eq!(m.to_string().parse::<TreeMethod>().unwrap(), *m);
+    }
+    // Aliases parse but are not listed.
+    assert_eq!("average".parse::<TreeMethod>().unwrap(), TreeMethod::Upgma);
+    assert_eq!(
+        "avg".parse::<TreeMethod>().unwrap_err().to_string(),
+        "unknown tree method: avg (expected one of: upgma, single, complete, nj)"
+    );
+}
+
+#[test]
+fn from_names_defaults_and_overrides() {
+    let d = NcdOptions::from_names(None, None, None, None).unwrap();
+    assert_eq!(d.join, Join::Frame64);
+    assert_eq!(d.symmetry, Symmetry::Min);
+    assert_eq!(d.metric, Metric::Ncd);
+    assert!(!d.clamp_0_1);
+
+    let o = NcdOptions::from_names(Some("frame64"), Some("none"), Some("cdm"), Some(true)).unwrap();
+    assert_eq!(o.symmetry, Symmetry::None);
+    assert_eq!(o.metric, Metric::Cdm);
+    assert!(o.clamp_0_1);
+}
+
+#[test]
+fn unknown_names_list_the_choices() {
+    let err = NcdOptions::from_names(None, Some("mean"), None, None).unwrap_err();
+    assert!(matches!(err, NcdError::InvalidSpec(_)), "{err:?}");
+    assert_eq!(
+        err.to_string(),
+        "unknown symmetry: mean (expected one of: none, min, avg, max)"
+    );
+
+    let err = "nid".parse::<Metric>().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unknown metric: nid (expected one of: ncd, cdm, clm)"
+    );
+
+    let err = "append".parse::<Join>().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unknown join: append (expected one of: frame64, concat, sep, interleave)"
+    );
+}
@@ -0,0 +1,150 @@
+use ncdprime_core::{Compressor, CompressorSpec, NcdOptions, ncd};
+
+mod common;
+use common::noise;
+
+fn pair(id: &str) -> (Box<dyn Compressor>, Box<dyn Compressor>) {
+    let framed: CompressorSpec = id.parse().unwrap();
+    let raw: CompressorSpec = format!("{id}-raw").parse().unwrap();
+    assert_eq!(raw.id(), format!("{id}-raw"));
+    (framed.build(), raw.build())
+}
+
+fn inputs() -> Vec<Vec<u8>> {
+    vec![
+        Vec::new(),
+        b"hello world".to_vec(),
+        b"the quick brown fox jumps over the lazy dog\n".repeat(50),
+        noise(7, 300),
+    ]
+}
+
+#[test]
+fn raw_sizes_drop_exactly_the_container() {
+    // gzip: 10-byte header + 8-byte CRC32/ISIZE trailer; lz4: 4-byte length prefix.
+    for (id, overhead) in [("gzip", 18), ("lz4", 4)] {
+        let (framed, raw) = pair(id);
+        for x in inputs() {
+            let (f, r) = (
+                framed.compressed_len(&x).unwrap(),
+                raw.compressed_len(&x).unwrap(),
+            );
+            assert_eq!(f - r, overhead, "{id} on {} bytes", x.len());
+        }
+    }
+
+    // zstd: at least the 4-byte magic; xz: at least stream header + footer.
+    for (id, at_least) in [("zstd", 4), ("xz", 24)] {
+        let (framed, raw) = pair(id);
+        for x in inputs() {
+            let (f, r) = (
+                framed.compressed_len(&x).unwrap(),
+                raw.compressed_len(&x).unwrap(),
+            );
+            assert!(f >= r + at_least, "{id} on {} bytes: {f} vs {r}", x.len());
+        }
+    }
+}
+
+/// A constant overhead h cancels in C(xy) - min(C(x), C(y)) but inflates the
+/// max(C(x), C(y)) denominator, so framed distances are the raw ones scaled by
+/// max / (max + h): every distance is pulled toward 0, most on small inputs.
+/// Raw identity distances are therefore *larger* than framed ones; what they
+/// remove is the input-size-dependent shrinkage.
+#[test]
+fn framing_scales_distances_toward_zero() {
+    let opts = NcdOptions {
+        symmetry: ncdprime_core::Symmetry::None,
+        ..NcdOptions::default()
+    };
+    let (framed, raw) = pair("gzip");
+    let x = b"the quick brown fox jumps over the lazy dog".to_vec();
+    let y = b"the quick brown cat naps under the lazy dog".to_vec();
+
+    for (a, b) in [(&x, &x), (&x, &y)] {
+        let join = |c: &dyn Compressor| {
+            let mut buf = Vec::new();
+            for part in [a, b] {
+                buf.extend_from_slice(&(part.len() as u64).to_le_bytes());
+                buf.extend_from_slice(part);
+            }
+            c.compressed_len(&buf).unwrap() as f64
+        };
+        let (ra, rb) = (
+            raw.compressed_len(a).unwrap() as f64,
+            raw.compressed_len(b).unwrap() as f64,
+        );
+        let expected = (join(&*raw) - ra.min(rb)) / (ra.max(rb) + 18.0);
+
+        let d_framed = ncd(&*framed, a, b, opts).unwrap();
+        let d_raw = ncd(&*raw, a, b, opts).unwrap();
+        assert!(
+            (d_framed - expected).abs() < 1e-12,
+            "{d_framed} vs {expected}"
+        );
+        assert!(d_raw > d_framed, "raw {d_raw} vs framed {d_framed}");
+    }
+}
+
+#[test]
+fn unrelated_inputs_are_no_longer_pulled_below_one() {
+    let (x, y) = (noise(1, 200), noise(2, 200));
+    for id in ["gzip", "zstd", "lz4", "xz"] {
+        let (framed, raw) = pair(id);
+        let d_framed = ncd(&*framed, &x, &y, NcdOptions::default()).unwrap();
+        let d_raw = ncd(&*raw, &x, &y, NcdOptions::default()).unwrap();
+        assert!(d_raw > d_framed, "{id}: raw {d_raw} vs framed {d_framed}");
+        assert!(
+            d_raw >= 1.0,
+            "{id}: incompressible inputs share nothing: {d_raw}"
+        );
+    }
+}
+
+/// Where raw variants help: the gap between identical and unrelated small
+/// items. Framing shrinks both distances by the same factor, so it narrows the
+/// range a classifier has to work with.
+#[test]
+fn raw_widens_the_gap_between_identical_and_unrelated_small_items() {
+    let (x, y) = (noise(1, 32), noise(2, 32));
+    for id in ["gzip", "zstd", "lz4", "xz"] {
+        let (framed, raw) = pair(id);
+        let gap = |c: &dyn Compressor| {
+            let opts = NcdOptions::default();
+            ncd(c, &x, &y, opts).unwrap() - ncd(c, &x, &x, opts).unwrap()
+        };
+        let (g_framed, g_raw) = (gap(&*framed), gap(&*raw));
+        assert!(
+            g_raw > g_framed,
+            "{id}: raw gap {g_raw} vs framed {g_framed}"
+        );
+    }
+}
+
+/// With the plain concat join nothing but the container is added, so raw
+/// distances between unrelated items sit near 1 at every size, while framed
+/// ones drift down as the items get smaller.
+#[test]
+fn raw_distances_between_unrelated_items_do_not_drift_with_size() {
+    let opts = NcdOptions {
+        join: ncdprime_core::Join::Concat,
+        ..NcdOptions::default()
+    };
+    for id in ["gzip", "zstd", "lz4", "xz"] {
+        let (framed, raw) = pair(id);
+        let spread = |c: &dyn Compressor| {
+            let ds: Vec<f64> = [32, 128, 1024]
+                .iter()
+                .map(|&n| ncd(c, &noise(1, n), &noise(2, n), opts).unwrap())
+                .collect();
+            let max = ds.iter().copied().fold(f64::MIN, f64::max);
+            let min = ds.iter().copied().fold(f64::MAX, f64::min);
+            max - min
+        };
+        let (s_framed, s_raw) = (spread(&*framed), spread(&*raw));
+        assert!(
+            s_raw < s_framed,
+            "{id}: raw spread {s_raw} vs framed {s_framed}"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use ncdprime_core::{
+    Gzip, MatrixOptions, NcdError, NcdOptions, Symmetry, ncd, ncd_sequence,
+    ncd_sequence_with_progress, sliding_windows,
+};
+
+mod common;
+use common::CountingCompressor;
+
+fn versions() -> Vec<Vec<u8>> {
+    let base = b"fn main() { println!(\"hello\"); }\n".repeat(8);
+    let mut edited = base.clone();
+    edited[10..15].copy_from_slice(b"start");
+    vec![
+        base.clone(),
+        edited,
+        base,
+        b"entirely different contents, nothing like the rest".repeat(4),
+    ]
+}
+
+#[test]
+fn consecutive_pairs_match_ncd() {
+    let c = Gzip::new(9);
+    let items = versions();
+    let opts = NcdOptions::default();
+    for threads in [1, 3] {
+        let series = ncd_sequence(&c, &items, opts, &MatrixOptions { threads }).unwrap();
+        assert_eq!(series.len(), items.len() - 1);
+        for (i, d) in series.iter().enumerate() {
+            let want = ncd(&c, &items[i], &items[i + 1], opts).unwrap();
+            assert_eq!(d.to_bits(), want.to_bits(), "pair {i}, {threads} threads");
+        }
+    }
+
+    let series = ncd_sequence(&c, &items, opts, &MatrixOptions::serial()).unwrap();
+    assert!(series[2] > series[0] && series[2] > series[1], "{series:?}");
+
+    assert!(
+        ncd_sequence(&c, &items[..1], opts, &MatrixOptions::serial())
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn each_item_is_compressed_once() {
+    let c = CountingCompressor::default();
+    let items: Vec<Vec<u8>> = (0..5).map(|i| vec![b'a' + i; 10]).collect();
+    let opts = NcdOptions {
+        symmetry: Symmetry::None,
+        ..NcdOptions::default()
+    };
+    let mut cells = Vec::new();
+    ncd_sequence_with_progress(&c, &items, opts, &MatrixOptions::serial(), |p| {
+        cells.push((p.row, p.col));
+    })
+    .unwrap();
+    assert_eq!(cells, [(0, 1), (1, 2), (2, 3), (3, 4)]);
+    // 5 singletons, then one joined pair per cell.
+    assert_eq!(c.calls(), 5 + 4);
+}
+
+#[test]
+fn windows_cover_the_input() {
+    let data: Vec<u8> = (0..10).collect();
+    let w = sliding_windows(&data, 4, 3).unwrap();
+    assert_eq!(w, [vec![0, 1, 2, 3], vec![3, 4, 5, 6], vec![6, 7, 8, 9]]);
+
+    let w = sliding_windows(&data, 4, 4).unwrap();
+    assert_eq!(w, [vec![0, 1, 2, 3], vec![4, 5, 6, 7]]);
+
+    assert_eq!(sliding_windows(&data, 64, 8).unwrap(), [&data[..]]);
+
+    let err = sliding_windows(&data, 4, 0).unwrap_err();
+    assert!(matches!(err, NcdError::InvalidSpec(_)), "{err:?}");
+}
+
+#[test]
+fn windows_borrow_from_the_input() {
+    let data = versions().concat();
+    let windows = sliding_windows(&data, 128, 32).unwrap();
+    assert!(
+        windows
+            .iter()
+            .all(|w| data.as_ptr_range().contains(&w.as_ptr()))
+    );
+
+    let c = Gzip::new(9);
+    let opts = NcdOptions::default();
+    let owned: Vec<Vec<u8>> = windows.iter().map(|w| w.to_vec()).collect();
+    assert_eq!(
+        ncd_sequence(&c, &windows, opts, &MatrixOptions::serial()).unwrap(),
+        ncd_sequence(&c, &owned, opts, &MatrixOptions::serial()).unwrap()
+    );
+}
@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use ncdprime_core::{CachedCompressor, CompressorSpec, NcdOptions, SizeCache, ncd, ncd_matrix};
+
+mod common;
+use common::CountingCompressor;
+
+fn temp_cache(name: &str) -> PathBuf {
+    let p = std::env::temp_dir()
+        .join(format!("ncdprime-test-{}", std::process::id()))
+        .join(format!("{name}.bin"));
+    let _ = std::fs::remove_file(&p);
+    p
+}
+
+#[test]
+fn sizes_survive_reopen() {
+    let path = temp_cache("reopen");
+    let spec = CompressorSpec::Gzip { level: 9 };
+    let a = vec![b"aaa".to_vec(), b"bbb".to_vec()];
+    let b = vec![b"ccc".to_vec()];
+
+    let c = CountingCompressor::default();
+    let first = {
+        let cache = SizeCache::open(&path, None).unwrap();
+        let m = ncd_matrix(
+            &CachedCompressor::new(&c, &spec, &cache),
+            &a,
+            &b,
+            NcdOptions::default(),
+        )
+        .unwrap();
+        cache.save().unwrap();
+        m
+    };
+    let cold_calls = c.calls();
+    assert!(cold_calls > 0);
+
+    let cache = SizeCache::open(&path, None).unwrap();
+    let second = ncd_matrix(
+        &CachedCompressor::new(&c, &spec, &cache),
+        &a,
+        &b,
+        NcdOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(first, second);
+    assert_eq!(c.calls(), cold_calls, "warm run recompressed");
+    assert_eq!(cache.stats().misses, 0);
+}
+
+#[test]
+fn spec_parameters_are_part_of_the_key() {
+    let path = temp_cache("spec");
+    let cache = SizeCache::open(&path, None).unwrap();
+    let c = CountingCompressor::default();
+
+    let g6 = CompressorSpec::Gzip { level: 6 };
+    let g9 = CompressorSpec::Gzip { level: 9 };
+    ncd(
+        &CachedCompressor::new(&c, &g6, &cache),
+        b"x",
+        b"y",
+        NcdOptions::default(),
+    )
+    .unwrap();
+    let after_g6 = c.calls();
+    ncd(
+        &CachedCompressor::new(&c, &g9, &cache),
+        b"x",
+        b"y",
+        NcdOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(c.calls(), 2 * after_g6);
+}
+
+#[test]
+fn max_entries_evicts_oldest() {
+    let path = temp_cache("evict");
+    let spec = CompressorSpec::default();
+
+    let cache = SizeCache::open(&path, Some(2)).unwrap();
+    for (i, input) in [b"one", b"two", b"thr"].iter().enumerate() {
+        cache.insert(SizeCache::key(&spec, *input), i as u64);
+    }
+    cache.save().unwrap();
+
+    let cache = SizeCache::open(&path, Some(2)).unwrap();
+    assert_eq!(cache.stats().entries, 2);
+    assert_eq!(cache.get(&SizeCache::key(&spec, b"one")), None);
+    assert_eq!(cache.get(&SizeCache::key(&spec, b"thr")), Some(2));
+
+    cache.clear().unwrap();
+    assert_eq!(cache.stats().entries, 0);
+    assert!(!path.exists());
+}
+
+#[test]
+fn rejects_foreign_files() {
+    let path = temp_cache("foreign");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, b"definitely not a cache").unwrap();
+    assert!(SizeCache::open(&path, None).is_err());
+}
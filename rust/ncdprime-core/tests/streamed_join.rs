@@ -0,0 +1,81 @@
+use std::io::Write;
+
+use ncdprime_core::{
+    CompressorSpec, CountingWriter, Join, SizeCache, compressor_ids, join_bytes, joined_len,
+    train_zstd_dictionary,
+};
+
+fn inputs() -> Vec<Vec<u8>> {
+    vec![
+        Vec::new(),
+        b"x".to_vec(),
+        (0..3000u32)
+            .flat_map(|i| format!("row {} {}\n", i % 37, i * 7 % 101).into_bytes())
+            .collect(),
+        (0..16_000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect(),
+    ]
+}
+
+fn specs() -> Vec<CompressorSpec> {
+    let samples: Vec<Vec<u8>> = (0..500)
+        .map(|i| format!("event id={i} user=u{} ok", i % 13).into_bytes())
+        .collect();
+    let dir = std::env::temp_dir().join(format!("ncdprime-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let dict = dir.join("streamed.dict");
+    std::fs::write(&dict, train_zstd_dictionary(&samples, 2048).unwrap()).unwrap();
+
+    compressor_ids()
+        .iter()
+        .map(|id| match *id {
+            "zstd-dict" => format!("zstd-dict:dict={}", dict.display()).parse(),
+            id => id.parse(),
+        })
+        // Brotli's one-pass quality 0 output depends on how its input is chunked.
+        .chain(["brotli:q=0".parse()])
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[test]
+fn streamed_join_matches_joined_buffer() {
+    let inputs = inputs();
+    for spec in specs() {
+        let c = spec.build();
+        for &join in Join::ALL {
+            for x in &inputs {
+                for y in &inputs {
+                    let streamed = joined_len(&*c, x, y, join).unwrap();
+                    let copied = c.compressed_len(&join_bytes(x, y, join)).unwrap();
+                    assert_eq!(
+                        streamed,
+                        copied,
+                        "{spec} {join}: |x|={} |y|={}",
+                        x.len(),
+                        y.len()
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn part_keys_match_joined_buffer_keys() {
+    let spec = CompressorSpec::Gzip { level: 9 };
+    let (x, y) = (b"abc".as_slice(), b"defgh".as_slice());
+    assert_eq!(
+        SizeCache::key_parts(&spec, &[x, y]),
+        SizeCache::key(&spec, b"abcdefgh")
+    );
+}
+
+#[test]
+fn counting_writer_counts() {
+    let mut w = CountingWriter::default();
+    w.write_all(b"hello").unwrap();
+    write!(w, " {}", 42).unwrap();
+    assert_eq!(w.written(), 8);
+}
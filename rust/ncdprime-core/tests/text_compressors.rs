@@ -0,0 +1,67 @@
+use ncdprime_core::{CompressorSpec, NcdError, NcdOptions, ncd};
+
+mod common;
+use common::{noise, prose};
+
+#[test]
+fn specs_round_trip_with_defaults() {
+    let bz: CompressorSpec = "bzip2".parse().unwrap();
+    assert_eq!(bz, CompressorSpec::Bzip2 { level: 9 });
+    assert_eq!(bz.to_string(), "bzip2:level=9");
+
+    let ppmd: CompressorSpec = "ppmd:o=8".parse().unwrap();
+    assert_eq!(
+        ppmd,
+        CompressorSpec::Ppmd {
+            order: 8,
+            mem_mb: 16
+        }
+    );
+    assert_eq!(ppmd.to_string(), "ppmd:order=8,mem=16");
+    assert_eq!(ppmd.to_string().parse::<CompressorSpec>().unwrap(), ppmd);
+}
+
+#[test]
+fn out_of_range_values_are_rejected() {
+    for spec in [
+        "bzip2:level=0",
+        "ppmd:order=1",
+        "ppmd:order=65",
+        "ppmd:mem=0",
+    ] {
+        let err = spec.parse::<CompressorSpec>().unwrap_err();
+        assert!(
+            matches!(err, NcdError::OutOfRange { .. }),
+            "{spec}: {err:?}"
+        );
+    }
+}
+
+#[test]
+fn text_compresses_far_better_than_noise() {
+    let text = prose(0, 2000);
+    let random = noise(0x9e37_79b9, text.len());
+    for spec in ["bzip2", "ppmd", "ppmd:order=2"] {
+        let c = spec.parse::<CompressorSpec>().unwrap().build();
+        let t = c.compressed_len(&text).unwrap();
+        let r = c.compressed_len(&random).unwrap();
+        assert!(t * 10 < r, "{spec}: text {t} vs noise {r}");
+    }
+}
+
+#[test]
+fn related_text_is_closer_than_unrelated() {
+    let a = prose(0, 1500);
+    let b = prose(3, 1500);
+    let random = noise(0x9e37_79b9, a.len());
+    for spec in ["bzip2", "ppmd"] {
+        let c = spec.parse::<CompressorSpec>().unwrap().build();
+        let opts = NcdOptions::default();
+        let same = ncd(&*c, &a, &a, opts).unwrap();
+        let near = ncd(&*c, &a, &b, opts).unwrap();
+        let far = ncd(&*c, &a, &random, opts).unwrap();
+        assert!(same < 0.2, "{spec}: NCD(x, x) = {same}");
+        assert!(near < far, "{spec}: related {near} vs unrelated {far}");
+        assert!(far > 0.8, "{spec}: unrelated {far}");
+    }
+}
@@ -0,0 +1,220 @@
+use ncdprime_core::{
+    Gzip, MatrixOptions, NcdError, NcdOptions, Tree, TreeMethod, build_tree, ncd_matrix_square,
+};
+
+/// Two tight pairs, {0, 1} and {2, 3}, far from each other.
+fn two_pairs() -> Vec<Vec<f64>> {
+    vec![
+        vec![0.0, 0.2, 0.8, 0.8],
+        vec![0.2, 0.0, 0.8, 0.8],
+        vec![0.8, 0.8, 0.0, 0.4],
+        vec![0.8, 0.8, 0.4, 0.0],
+    ]
+}
+
+#[test]
+fn method_names_round_trip() {
+    for &m in TreeMethod::ALL {
+        assert_eq!(m.name().parse::<TreeMethod>().unwrap(), m);
+    }
+    assert_eq!("average".parse::<TreeMethod>().unwrap(), TreeMethod::Upgma);
+    let err = "ward".parse::<TreeMethod>().unwrap_err();
+    assert!(
+        err.to_string().contains("upgma, single, complete, nj"),
+        "{err}"
+    );
+}
+
+#[test]
+fn upgma_heights_are_half_the_distance() {
+    let tree = build_tree(&two_pairs(), TreeMethod::Upgma).unwrap();
+    let labels = ["a", "b", "c", "d"];
+    assert_eq!(
+        tree.to_newick(&labels),
+        "((a:0.100000,b:0.100000):0.300000,(c:0.200000,d:0.200000):0.200000);"
+    );
+}
+
+#[test]
+fn linkages_differ_on_chained_items() {
+    // 0-1-2 form a chain; 3 is 0.5 from 0 but far from the rest.
+    let d = vec![
+        vec![0.0, 0.3, 0.6, 0.5],
+        vec![0.3, 0.0, 0.3, 0.9],
+        vec![0.6, 0.3, 0.0, 0.9],
+        vec![0.5, 0.9, 0.9, 0.0],
+    ];
+    let labels = ["a", "b", "c", "d"];
+    let single = build_tree(&d, TreeMethod::Single).unwrap();
+    assert_eq!(
+        single.to_newick(&labels),
+        "(((a:0.150000,b:0.150000):0.000000,c:0.150000):0.100000,d:0.250000);"
+    );
+    let complete = build_tree(&d, TreeMethod::Complete).unwrap();
+    assert_eq!(
+        complete.to_newick(&labels),
+        "(((a:0.150000,b:0.150000):0.150000,c:0.300000):0.150000,d:0.450000);"
+    );
+}
+
+#[test]
+fn nj_recovers_an_additive_tree() {
+    // Path lengths in ((a:1,b:2):1,c:3,(d:1,e:2):2).
+    let d = vec![
+        vec![0.0, 3.0, 5.0, 5.0, 6.0],
+        vec![3.0, 0.0, 6.0, 6.0, 7.0],
+        vec![5.0, 6.0, 0.0, 6.0, 7.0],
+        vec![5.0, 6.0, 6.0, 0.0, 3.0],
+        vec![6.0, 7.0, 7.0, 3.0, 0.0],
+    ];
+    let tree = build_tree(&d, TreeMethod::Nj).unwrap();
+    let Tree::Node(children) = &tree else {
+        panic!("expected a root node, got {tree:?}");
+    };
+    assert_eq!(children.len(), 3, "{tree:?}");
+
+    // Every pairwise path length matches the input.
+    let paths = path_lengths(&tree, d.len());
+    for i in 0..d.len() {
+        for j in 0..d.len() {
+            assert!(
+                (paths[i][j] - d[i][j]).abs() < 1e-9,
+                "({i}, {j}): {paths:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn small_and_asymmetric_inputs() {
+    assert_eq!(
+        build_tree(&[vec![0.0]], TreeMethod::Nj).unwrap(),
+        Tree::Leaf(0)
+    );
+    for &m in TreeMethod::ALL {
+        let tree = build_tree(&[vec![0.0, 0.4], vec![0.6, 0.0]], m).unwrap();
+        assert_eq!(
+            tree.to_newick(&["x", "y"]),
+            "(x:0.250000,y:0.250000);",
+            "{m}"
+        );
+    }
+}
+
+#[test]
+fn labels_are_quoted_when_needed() {
+    let tree = build_tree(&two_pairs(), TreeMethod::Upgma).unwrap();
+    let labels = ["plain.txt", "two words", "it's", "a,b"];
+    let newick = tree.to_newick(&labels);
+    assert!(newick.starts_with("((plain.txt:"), "{newick}");
+    assert!(newick.contains("'two words':"), "{newick}");
+    assert!(newick.contains("'it''s':"), "{newick}");
+    assert!(newick.contains("'a,b':"), "{newick}");
+}
+
+#[test]
+fn rejects_non_square_matrices() {
+    let err = build_tree(&[], TreeMethod::Upgma).unwrap_err();
+    assert!(matches!(err, NcdError::InvalidSpec(_)), "{err:?}");
+    let err = build_tree(&[vec![0.0, 1.0], vec![1.0]], TreeMethod::Upgma).unwrap_err();
+    assert!(err.to_string().contains("row 1"), "{err}");
+}
+
+#[test]
+fn rejects_non_finite_distances() {
+    for bad in [f64::NAN, f64::INFINITY] {
+        let mut d = two_pairs();
+        d[2][3] = bad;
+        for &method in TreeMethod::ALL {
+            let err = build_tree(&d, method).unwrap_err();
+            assert!(matches!(err, NcdError::InvalidSpec(_)), "{err:?}");
+        }
+    }
+}
+
+#[test]
+fn overflowing_nj_scores_still_join_active_clusters() {
+    // Finite distances whose neighbor-joining scores overflow to infinity.
+    let d: Vec<Vec<f64>> = (0..6)
+        .map(|i| {
+            (0..6)
+                .map(|j| if i == j { 0.0 } else { f64::MAX })
+                .collect()
+        })
+        .collect();
+    for &method in TreeMethod::ALL {
+        let mut leaves = build_tree(&d, method).unwrap().leaves();
+        leaves.sort();
+        assert_eq!(leaves, (0..6).collect::<Vec<_>>(), "{method}");
+    }
+}
+
+#[test]
+fn clusters_compressed_inputs() {
+    let items: Vec<Vec<u8>> = [
+        "the quick brown fox jumps over the lazy dog",
+        "the quick brown fox leaps over the lazy dog",
+        "lorem ipsum dolor sit amet, consectetur adipiscing",
+        "lorem ipsum dolor sit amet, consectetur elit",
+    ]
+    .iter()
+    .map(|s| s.repeat(4).into_bytes())
+    .collect();
+    let d = ncd_matrix_square(
+        &Gzip::new(9),
+        &items,
+        NcdOptions::default(),
+        &MatrixOptions::serial(),
+    )
+    .unwrap();
+    for &m in TreeMethod::ALL {
+        let tree = build_tree(&d, m).unwrap();
+        let mut leaves = tree.leaves();
+        leaves.sort();
+        assert_eq!(leaves, [0, 1, 2, 3], "{m}");
+        if m != TreeMethod::Nj {
+            let Tree::Node(children) = &tree else {
+                panic!("{m}: {tree:?}");
+            };
+            let mut sides: Vec<Vec<usize>> = children.iter().map(|(c, _)| c.leaves()).collect();
+            sides.iter_mut().for_each(|s| s.sort());
+            sides.sort();
+            assert_eq!(sides, [vec![0, 1], vec![2, 3]], "{m}");
+        }
+    }
+}
+
+/// Path length between every pair of leaves.
+fn path_lengths(tree: &Tree, n: usize) -> Vec<Vec<f64>> {
+    // Depth of every leaf below each node on its path, gathered bottom-up.
+    fn walk(tree: &Tree, out: &mut Vec<Vec<f64>>) -> Vec<(usize, f64)> {
+        match tree {
+            Tree::Leaf(i) => vec![(*i, 0.0)],
+            Tree::Node(children) => {
+                let groups: Vec<Vec<(usize, f64)>> = children
+                    .iter()
+                    .map(|(c, len)| {
+                        walk(c, out)
+                            .into_iter()
+                            .map(|(i, d)| (i, d + len))
+                            .collect()
+                    })
+                    .collect();
+                for (g, a) in groups.iter().enumerate() {
+                    for b in &groups[g + 1..] {
+                        for &(i, di) in a {
+                            for &(j, dj) in b {
+                                out[i][j] = di + dj;
+                                out[j][i] = di + dj;
+                            }
+                        }
+                    }
+                }
+                groups.concat()
+            }
+        }
+    }
+    let mut out = vec![vec![0.0; n]; n];
+    walk(tree, &mut out);
+    out
+}
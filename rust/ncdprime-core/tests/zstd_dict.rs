@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+
+use ncdprime_core::{CompressorSpec, NcdOptions, Zstd, ZstdDictionary, ncd, train_zstd_dictionary};
+
+/// Short, templated event lines: the kind of input where plain compressors
+/// spend most of their output on headers and literals.
+fn events(n: usize) -> Vec<Vec<u8>> {
+    let users = ["alice", "bob", "carol", "dave"];
+    let actions = ["login", "logout", "upload", "download", "delete"];
+    (0..n)
+        .map(|i| {
+            format!(
+                "user {} {} ok id={}",
+                users[i % users.len()],
+                actions[(i / users.len()) % actions.len()],
+                (i * 7919) % 10000
+            )
+            .into_bytes()
+        })
+        .collect()
+}
+
+fn temp_dict(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ncdprime-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    let dict = train_zstd_dictionary(&events(2000), 2048).unwrap();
+    std::fs::write(&path, dict).unwrap();
+    path
+}
+
+#[test]
+fn dictionary_helps_short_inputs() {
+    let path = temp_dict("short.dict");
+    let spec: CompressorSpec = format!("zstd-dict:level=3,dict={}", path.display())
+        .parse()
+        .unwrap();
+    let with_dict = spec.build();
+    let plain = Zstd::new(3);
+    let opts = NcdOptions::default();
+
+    let x = b"user alice login ok id=4711";
+    let near = b"user alice login ok id=4712";
+    let far = b"user dave delete ok id=8";
+    let unrelated = b"kernel: eth0 link down";
+
+    let d_plain = ncd(&plain, x, x, opts).unwrap();
+    let d = |y: &[u8]| ncd(&*with_dict, x, y, opts).unwrap();
+    assert!(d(x) < d_plain, "identity {} vs plain {d_plain}", d(x));
+    assert!(d(x) < d(near) && d(near) < d(far) && d(far) < d(unrelated));
+    assert!(d(unrelated) <= 1.0);
+}
+
+#[test]
+fn spec_round_trips_and_caches_by_content() {
+    let path = temp_dict("roundtrip.dict");
+    let copy = path.with_file_name("roundtrip-copy.dict");
+    std::fs::copy(&path, &copy).unwrap();
+
+    let spec: CompressorSpec = format!("zstd-dict:dict={}", path.display())
+        .parse()
+        .unwrap();
+    assert_eq!(spec.id(), "zstd-dict");
+    assert_eq!(
+        spec.to_string(),
+        format!("zstd-dict:level=3,dict={}", path.display())
+    );
+    assert_eq!(spec.to_string().parse::<CompressorSpec>().unwrap(), spec);
+
+    // The same dictionary under another path is the same compressor.
+    let moved = CompressorSpec::zstd_dict(ZstdDictionary::load(&copy).unwrap(), 3).unwrap();
+    assert_eq!(moved.cache_key(), spec.cache_key());
+    assert!(!spec.cache_key().contains(&*path.to_string_lossy()));
+}
+
+#[test]
+fn zstd_dict_errors_are_descriptive() {
+    let err = CompressorSpec::from_params("zstd-dict", []).unwrap_err();
+    assert!(
+        err.to_string().contains("zstd-dict needs a dictionary"),
+        "{err}"
+    );
+
+    let err = "zstd-dict:level=3".parse::<CompressorSpec>().unwrap_err();
+    assert!(
+        err.to_string().contains("zstd-dict needs a dictionary"),
+        "{err}"
+    );
+
+    let err = "zstd:dict=x.dict".parse::<CompressorSpec>().unwrap_err();
+    assert_eq!(err.to_string(), "zstd does not take a dictionary");
+
+    let err = "zstd-dict:dict=/nonexistent/ncdprime.dict"
+        .parse::<CompressorSpec>()
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("/nonexistent/ncdprime.dict"),
+        "{err}"
+    );
+}
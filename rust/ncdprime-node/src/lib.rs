@@ -1,23 +1,151 @@
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
+use std::collections::HashMap;
+
+/// NCD variant settings; omitted fields use the core defaults.
+#[napi(object)]
+pub struct NcdOptions {
+    /// `"frame64"` | `"concat"` | `"sep[:HEX]"` | `"interleave[:BYTES]"`
+    pub join: Option<String>,
+    /// `"none"` | `"min"` | `"avg"` | `"max"`
+    pub symmetry: Option<String>,
+    /// `"ncd"` | `"cdm"` | `"clm"`
+    pub metric: Option<String>,
+    pub clamp: Option<bool>,
+    /// Compressor spec string (`"zstd:level=19"`) or object (`{ id: "zstd", level: 19 }`).
+    /// Overrides `gzipLevel` when set.
+    pub compressor: Option<Either<String, Object>>,
+}
+
+/// A compressor id and its tunable parameters with their defaults.
+#[napi(object)]
+pub struct CompressorInfo {
+    pub id: String,
+    pub params: HashMap<String, i64>,
+}
+
+fn invalid_arg(e: impl std::fmt::Display) -> Error {
+    Error::new(Status::InvalidArg, e.to_string())
+}
+
+/// Bad specs, options and oversized inputs throw with `code: "InvalidArg"`;
+/// compressor and I/O failures with `"GenericFailure"`.
+fn ncd_err(e: ncdprime_core::NcdError) -> Error {
+    use ncdprime_core::NcdError;
+
+    let status = match &e {
+        e if e.is_invalid_input() => Status::InvalidArg,
+        NcdError::Cancelled => Status::Cancelled,
+        _ => Status::GenericFailure,
+    };
+    Error::new(status, e.to_string())
+}
+
+fn compressor_spec(
+    compressor: Option<&Either<String, Object>>,
+    gzip_level: Option<u32>,
+) -> Result<ncdprime_core::CompressorSpec> {
+    match compressor {
+        // Validated like any other level; without one gzip keeps its default.
+        None => ncdprime_core::CompressorSpec::from_params(
+            "gzip",
+            gzip_level.map(|l| ("level", i64::from(l))),
+        )
+        .map_err(ncd_err),
+        Some(Either::A(spec)) => spec.parse().map_err(ncd_err),
+        Some(Either::B(obj)) => {
+            let id: String = obj
+                .get("id")?
+                .ok_or_else(|| invalid_arg("compressor spec needs an `id`"))?;
+            let mut params = Vec::new();
+            for key in Object::keys(obj)? {
+                if key == "id" {
+                    continue;
+                }
+                // Not `i64`: that would read `undefined` as 0 and truncate 1.5.
+                let v = obj
+                    .get::<_, f64>(&key)
+                    .ok()
+                    .flatten()
+                    .filter(|v| v.fract() == 0.0)
+                    .ok_or_else(|| {
+                        invalid_arg(format!("compressor parameter `{key}` must be an integer"))
+                    })?;
+                params.push((key, v as i64));
+            }
+            ncdprime_core::CompressorSpec::from_params(
+                &id,
+                params.iter().map(|(k, v)| (k.as_str(), *v)),
+            )
+            .map_err(ncd_err)
+        }
+    }
+}
+
+/// Resolve the NCD options and compressor spec from the optional `opts` argument.
+fn resolve(
+    opts: Option<NcdOptions>,
+    gzip_level: Option<u32>,
+) -> Result<(ncdprime_core::NcdOptions, ncdprime_core::CompressorSpec)> {
+    let opts = opts.unwrap_or(NcdOptions {
+        join: None,
+        symmetry: None,
+        metric: None,
+        clamp: None,
+        compressor: None,
+    });
+    let ncd_opts = ncdprime_core::NcdOptions::from_names(
+        opts.join.as_deref(),
+        opts.symmetry.as_deref(),
+        opts.metric.as_deref(),
+        opts.clamp,
+    )
+    .map_err(ncd_err)?;
+    let spec = compressor_spec(opts.compressor.as_ref(), gzip_level)?;
+    Ok((ncd_opts, spec))
+}
 
 #[napi]
-pub fn ncd(x: Buffer, y: Buffer, gzip_level: Option<u32>) -> Result<f64> {
-    let c = ncdprime_core::Gzip::new(gzip_level.unwrap_or(9));
-    let d = ncdprime_core::ncd(&c, &x, &y, ncdprime_core::NcdOptions::default())
-        .map_err(|e| Error::from_reason(format!("ncd failed: {e}")))?;
-    Ok(d)
+pub fn ncd(x: Buffer, y: Buffer, gzip_level: Option<u32>, opts: Option<NcdOptions>) -> Result<f64> {
+    let (opts, spec) = resolve(opts, gzip_level)?;
+    let c = spec.build();
+    ncdprime_core::ncd(&*c, &x, &y, opts).map_err(ncd_err)
 }
 
 #[napi]
-pub fn matrix(a: Vec<Buffer>, b: Vec<Buffer>, gzip_level: Option<u32>) -> Result<Vec<Vec<f64>>> {
-    let c = ncdprime_core::Gzip::new(gzip_level.unwrap_or(9));
+pub fn matrix(
+    a: Vec<Buffer>,
+    b: Vec<Buffer>,
+    gzip_level: Option<u32>,
+    opts: Option<NcdOptions>,
+) -> Result<Vec<Vec<f64>>> {
+    let (opts, spec) = resolve(opts, gzip_level)?;
+    let c = spec.build();
 
     let a_vecs: Vec<Vec<u8>> = a.into_iter().map(|buf| buf.to_vec()).collect();
     let b_vecs: Vec<Vec<u8>> = b.into_iter().map(|buf| buf.to_vec()).collect();
 
-    let out = ncdprime_core::ncd_matrix(&c, &a_vecs, &b_vecs, ncdprime_core::NcdOptions::default())
-        .map_err(|e| Error::from_reason(format!("matrix failed: {e}")))?;
+    ncdprime_core::ncd_matrix(&*c, &a_vecs, &b_vecs, opts).map_err(ncd_err)
+}
 
-    Ok(out)
+#[napi]
+pub fn compressors() -> Result<Vec<CompressorInfo>> {
+    ncdprime_core::compressor_ids()
+        .iter()
+        .map(|&id| {
+            let params = ncdprime_core::compressor_params(id).ok_or_else(|| {
+                Error::new(
+                    Status::GenericFailure,
+                    format!("no parameters listed for compressor {id}"),
+                )
+            })?;
+            Ok(CompressorInfo {
+                id: id.to_string(),
+                params: params
+                    .iter()
+                    .map(|p| (p.name.to_string(), p.default))
+                    .collect(),
+            })
+        })
+        .collect()
 }
@@ -1,35 +1,135 @@
-use ncdprime_core::{Gzip, NcdOptions};
+use ncdprime_core::{CompressorSpec, NcdError, NcdOptions};
 use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::HashMap;
+
+/// Bad specs, options and oversized inputs raise `ValueError`, compressor failures
+/// `RuntimeError`, I/O failures `OSError`.
+fn py_err(e: NcdError) -> PyErr {
+    use pyo3::exceptions::{PyInterruptedError, PyOSError, PyRuntimeError, PyValueError};
+
+    let msg = e.to_string();
+    match e {
+        NcdError::InvalidSpec(_) | NcdError::OutOfRange { .. } | NcdError::InputTooLarge { .. } => {
+            PyValueError::new_err(msg)
+        }
+        NcdError::Compressor { .. } => PyRuntimeError::new_err(msg),
+        NcdError::Cancelled => PyInterruptedError::new_err(msg),
+        NcdError::Io(_) => PyOSError::new_err(msg),
+    }
+}
+
+/// Resolve the `compressor` argument: a spec string such as `"zstd:level=19"`, or a dict such as
+/// `{"id": "zstd", "level": 19}`. Without it, gzip at `gzip_level` is used.
+fn compressor_spec(
+    compressor: Option<&Bound<'_, PyAny>>,
+    gzip_level: Option<u32>,
+) -> PyResult<CompressorSpec> {
+    let Some(compressor) = compressor else {
+        // Validated like any other level; without one gzip keeps its default.
+        let level = gzip_level.map(|l| ("level", i64::from(l)));
+        return CompressorSpec::from_params("gzip", level).map_err(py_err);
+    };
+    if let Ok(spec) = compressor.extract::<String>() {
+        return spec.parse().map_err(py_err);
+    }
+
+    let dict = compressor.downcast::<PyDict>().map_err(|_| {
+        pyo3::exceptions::PyTypeError::new_err("compressor must be a spec string or a spec dict")
+    })?;
+    let id: String = dict
+        .get_item("id")?
+        .ok_or_else(|| pyo3::exceptions::PyValueError::new_err("compressor spec needs an `id`"))?
+        .extract()?;
+    let mut params = Vec::new();
+    for (k, v) in dict.iter() {
+        let k: String = k.extract()?;
+        if k != "id" {
+            params.push((k, v.extract::<i64>()?));
+        }
+    }
+    CompressorSpec::from_params(&id, params.iter().map(|(k, v)| (k.as_str(), *v))).map_err(py_err)
+}
+
+/// Parse the by-name NCD options; unknown names raise `ValueError`.
+fn ncd_options(
+    join: Option<&str>,
+    symmetry: Option<&str>,
+    metric: Option<&str>,
+    clamp: Option<bool>,
+) -> PyResult<NcdOptions> {
+    NcdOptions::from_names(join, symmetry, metric, clamp).map_err(py_err)
+}
 
 /// Compute NCD between two byte strings.
 ///
+/// `compressor` is a spec string (`"zstd:level=19"`) or a spec dict (`{"id": "zstd", "level": 19}`).
+///
 /// Default behavior:
-/// - join=frame64
-/// - symmetry=min(C(xy), C(yx))
+/// - compressor=gzip (at `gzip_level`, default 9)
+/// - join=frame64 (also concat, sep[:HEX], interleave[:BYTES])
+/// - symmetry=min(C(xy), C(yx)) (also none, avg, max)
+/// - metric=ncd (also cdm, clm)
+/// - clamp=False
 #[pyfunction]
-#[pyo3(signature = (x, y, gzip_level=None))]
-fn ncd(x: &[u8], y: &[u8], gzip_level: Option<u32>) -> PyResult<f64> {
-    let c = Gzip::new(gzip_level.unwrap_or(9));
-    let d = ncdprime_core::ncd(&c, x, y, NcdOptions::default())
-        .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(format!("ncd failed: {e}")))?;
-    Ok(d)
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (x, y, gzip_level=None, *, compressor=None, join=None, symmetry=None, metric=None, clamp=None))]
+fn ncd(
+    x: &[u8],
+    y: &[u8],
+    gzip_level: Option<u32>,
+    compressor: Option<&Bound<'_, PyAny>>,
+    join: Option<&str>,
+    symmetry: Option<&str>,
+    metric: Option<&str>,
+    clamp: Option<bool>,
+) -> PyResult<f64> {
+    let opts = ncd_options(join, symmetry, metric, clamp)?;
+    let c = compressor_spec(compressor, gzip_level)?.build();
+    ncdprime_core::ncd(&*c, x, y, opts).map_err(py_err)
 }
 
 /// Compute an NCD matrix between two lists of byte strings.
 ///
 /// Returns a list-of-lists of floats (rows = a, cols = b).
 #[pyfunction]
-#[pyo3(signature = (a, b, gzip_level=None))]
-fn matrix(a: Vec<Vec<u8>>, b: Vec<Vec<u8>>, gzip_level: Option<u32>) -> PyResult<Vec<Vec<f64>>> {
-    let c = Gzip::new(gzip_level.unwrap_or(9));
-    let m = ncdprime_core::ncd_matrix(&c, &a, &b, NcdOptions::default())
-        .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(format!("matrix failed: {e}")))?;
-    Ok(m)
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (a, b, gzip_level=None, *, compressor=None, join=None, symmetry=None, metric=None, clamp=None))]
+fn matrix(
+    a: Vec<Vec<u8>>,
+    b: Vec<Vec<u8>>,
+    gzip_level: Option<u32>,
+    compressor: Option<&Bound<'_, PyAny>>,
+    join: Option<&str>,
+    symmetry: Option<&str>,
+    metric: Option<&str>,
+    clamp: Option<bool>,
+) -> PyResult<Vec<Vec<f64>>> {
+    let opts = ncd_options(join, symmetry, metric, clamp)?;
+    let c = compressor_spec(compressor, gzip_level)?.build();
+    ncdprime_core::ncd_matrix(&*c, &a, &b, opts).map_err(py_err)
+}
+
+/// List available compressors as `(id, {param: default})` pairs.
+#[pyfunction]
+fn compressors() -> Vec<(&'static str, HashMap<&'static str, i64>)> {
+    ncdprime_core::compressor_ids()
+        .iter()
+        .map(|&id| {
+            let params = ncdprime_core::compressor_params(id)
+                .unwrap_or_default()
+                .iter()
+                .map(|p| (p.name, p.default))
+                .collect();
+            (id, params)
+        })
+        .collect()
 }
 
 #[pymodule]
 fn ncdprime(_py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(ncd, m)?)?;
     m.add_function(wrap_pyfunction!(matrix, m)?)?;
+    m.add_function(wrap_pyfunction!(compressors, m)?)?;
     Ok(())
 }
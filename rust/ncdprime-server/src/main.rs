@@ -10,18 +10,141 @@ use serde::{Deserialize, Serialize};
 use std::{net::SocketAddr, sync::Arc};
 use tracing_subscriber::EnvFilter;
 
-#[derive(Clone)]
 struct AppState {
-    // Placeholder for future shared caches.
-    // For now we construct compressors per-request.
+    /// Persistent compressed-size cache, enabled by setting `NCDPRIME_CACHE`.
+    cache: Option<ncdprime_core::SizeCache>,
+}
+
+impl AppState {
+    /// Run `f` with `c`, routed through the size cache when one is configured.
+    fn with_cache<T>(
+        &self,
+        c: &dyn ncdprime_core::Compressor,
+        spec: &ncdprime_core::CompressorSpec,
+        f: impl FnOnce(&dyn ncdprime_core::Compressor) -> ncdprime_core::NcdResult<T>,
+    ) -> ncdprime_core::NcdResult<T> {
+        let Some(cache) = &self.cache else {
+            return f(c);
+        };
+        let out = f(&ncdprime_core::CachedCompressor::new(c, spec, cache));
+        if let Err(e) = cache.save() {
+            tracing::warn!("failed to save size cache: {e}");
+        }
+        out
+    }
+}
+
+/// NCD variant settings accepted by every endpoint; omitted fields use the core defaults.
+#[derive(Debug, Default, Deserialize)]
+struct NcdParams {
+    join: Option<String>,
+    symmetry: Option<String>,
+    metric: Option<String>,
+    clamp: Option<bool>,
+}
+
+impl NcdParams {
+    fn options(&self) -> ncdprime_core::NcdResult<ncdprime_core::NcdOptions> {
+        ncdprime_core::NcdOptions::from_names(
+            self.join.as_deref(),
+            self.symmetry.as_deref(),
+            self.metric.as_deref(),
+            self.clamp,
+        )
+    }
+}
+
+/// Either a spec string (`"zstd:level=19"`) or a spec object (`{"id": "zstd", "level": 19}`).
+///
+/// When omitted, requests fall back to gzip at `gzip_level`.
+fn compressor_spec(
+    value: Option<&serde_json::Value>,
+    gzip_level: u32,
+) -> Result<ncdprime_core::CompressorSpec, String> {
+    use serde_json::Value;
+
+    let spec = match value {
+        None => ncdprime_core::CompressorSpec::from_params("gzip", [("level", gzip_level.into())])
+            .map_err(|e| e.to_string()),
+        Some(Value::String(spec)) => {
+            let id = spec.split(':').next().unwrap_or_default().trim();
+            if !served(id) {
+                return Err(format!("{id} is not available over HTTP"));
+            }
+            spec.parse()
+                .map_err(|e: ncdprime_core::NcdError| e.to_string())
+        }
+        Some(Value::Object(obj)) => {
+            let id = obj
+                .get("id")
+                .and_then(Value::as_str)
+                .ok_or("compressor spec needs a string `id`")?;
+            if !served(id) {
+                return Err(format!("{id} is not available over HTTP"));
+            }
+            let mut params = Vec::new();
+            for (name, v) in obj.iter().filter(|(k, _)| *k != "id") {
+                let v = v
+                    .as_i64()
+                    .ok_or_else(|| format!("compressor parameter `{name}` must be an integer"))?;
+                params.push((name.as_str(), v));
+            }
+            ncdprime_core::CompressorSpec::from_params(id, params).map_err(|e| e.to_string())
+        }
+        Some(_) => Err("compressor must be an id string or a spec object".to_string()),
+    }?;
+    within_server_limits(&spec)?;
+    Ok(spec)
+}
+
+/// Most model memory (MiB) a request may give ppmd.
+const PPMD_MAX_MEM_MB: u32 = 256;
+/// Largest brotli window a request may ask for (4 MiB, brotli's default).
+const BROTLI_MAX_LGWIN: u32 = 22;
+/// Highest xz preset a request may ask for; 7 to 9 need 190 to 680 MiB each.
+const XZ_MAX_LEVEL: u32 = 6;
+
+/// Requests are unauthenticated, and these parameters make every compressor
+/// instance allocate that much however small the inputs are, so the server
+/// rejects values past the caps above instead of serving them.
+fn within_server_limits(spec: &ncdprime_core::CompressorSpec) -> Result<(), String> {
+    use ncdprime_core::CompressorSpec;
+
+    let (name, value, max) = match *spec {
+        CompressorSpec::Ppmd { mem_mb, .. } => ("ppmd mem", mem_mb, PPMD_MAX_MEM_MB),
+        CompressorSpec::Brotli { lgwin, .. } => ("brotli lgwin", lgwin, BROTLI_MAX_LGWIN),
+        CompressorSpec::Xz { level } | CompressorSpec::XzRaw { level } => {
+            ("xz level", level, XZ_MAX_LEVEL)
+        }
+        _ => return Ok(()),
+    };
+    if value > max {
+        return Err(format!("{name} is capped at {max} over HTTP, got {value}"));
+    }
+    Ok(())
+}
+
+/// `zstd-dict` loads its dictionary from a path, which clients must not pick.
+fn served(id: &str) -> bool {
+    id != "zstd-dict"
+}
+
+#[derive(Debug, Serialize)]
+struct CompressorInfo {
+    id: &'static str,
+    /// Parameter name -> default value.
+    params: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Deserialize)]
 struct PairRequest {
     a_b64: String,
     b_b64: String,
+    compressor: Option<serde_json::Value>,
     #[serde(default = "default_gzip_level")]
     gzip_level: u32,
+    #[serde(flatten)]
+    ncd: NcdParams,
 }
 
 fn default_gzip_level() -> u32 {
@@ -37,8 +160,11 @@ struct PairResponse {
 struct MatrixRequest {
     a: Vec<String>,
     b: Vec<String>,
+    compressor: Option<serde_json::Value>,
     #[serde(default = "default_gzip_level")]
     gzip_level: u32,
+    #[serde(flatten)]
+    ncd: NcdParams,
 }
 
 #[derive(Debug, Serialize)]
@@ -55,6 +181,41 @@ async fn health() -> &'static str {
     "ok"
 }
 
+async fn compressors() -> Json<Vec<CompressorInfo>> {
+    let infos = ncdprime_core::compressor_ids()
+        .iter()
+        .filter(|id| served(id))
+        .map(|&id| CompressorInfo {
+            id,
+            params: ncdprime_core::compressor_params(id)
+                .unwrap_or_default()
+                .iter()
+                .map(|p| (p.name.to_string(), p.default.into()))
+                .collect(),
+        })
+        .collect();
+    Json(infos)
+}
+
+fn bad_request(error: String) -> axum::response::Response {
+    (StatusCode::BAD_REQUEST, Json(ErrorResponse { error })).into_response()
+}
+
+/// Bad specs and options are the client's fault (400), oversized inputs get
+/// 413, and compressor or I/O failures are ours (500).
+fn ncd_error(e: ncdprime_core::NcdError) -> axum::response::Response {
+    use ncdprime_core::NcdError;
+
+    let status = match &e {
+        NcdError::InvalidSpec(_) | NcdError::OutOfRange { .. } => StatusCode::BAD_REQUEST,
+        NcdError::InputTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+        NcdError::Cancelled => StatusCode::SERVICE_UNAVAILABLE,
+        NcdError::Compressor { .. } | NcdError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    let error = e.to_string();
+    (status, Json(ErrorResponse { error })).into_response()
+}
+
 fn decode_b64(s: &str) -> anyhow::Result<Vec<u8>> {
     let bytes = base64::engine::general_purpose::STANDARD
         .decode(s)
@@ -62,63 +223,46 @@ fn decode_b64(s: &str) -> anyhow::Result<Vec<u8>> {
     Ok(bytes)
 }
 
-async fn pair(State(_st): State<Arc<AppState>>, Json(req): Json<PairRequest>) -> impl IntoResponse {
+async fn pair(State(st): State<Arc<AppState>>, Json(req): Json<PairRequest>) -> impl IntoResponse {
+    let opts = match req.ncd.options() {
+        Ok(o) => o,
+        Err(e) => return ncd_error(e),
+    };
+
     let a = match decode_b64(&req.a_b64) {
         Ok(v) => v,
-        Err(e) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: e.to_string(),
-                }),
-            )
-                .into_response();
-        }
+        Err(e) => return bad_request(e.to_string()),
     };
-
     let b = match decode_b64(&req.b_b64) {
         Ok(v) => v,
-        Err(e) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: e.to_string(),
-                }),
-            )
-                .into_response();
-        }
+        Err(e) => return bad_request(e.to_string()),
     };
 
-    let c = ncdprime_core::Gzip::new(req.gzip_level);
-    match ncdprime_core::ncd(&c, &a, &b, ncdprime_core::NcdOptions::default()) {
+    let spec = match compressor_spec(req.compressor.as_ref(), req.gzip_level) {
+        Ok(s) => s,
+        Err(e) => return bad_request(e),
+    };
+    let c = spec.build();
+    match st.with_cache(&*c, &spec, |c| ncdprime_core::ncd(c, &a, &b, opts)) {
         Ok(ncd) => (StatusCode::OK, Json(PairResponse { ncd })).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: e.to_string(),
-            }),
-        )
-            .into_response(),
+        Err(e) => ncd_error(e),
     }
 }
 
 async fn matrix(
-    State(_st): State<Arc<AppState>>,
+    State(st): State<Arc<AppState>>,
     Json(req): Json<MatrixRequest>,
 ) -> impl IntoResponse {
+    let opts = match req.ncd.options() {
+        Ok(o) => o,
+        Err(e) => return ncd_error(e),
+    };
+
     let mut a_vecs = Vec::with_capacity(req.a.len());
     for s in &req.a {
         match decode_b64(s) {
             Ok(v) => a_vecs.push(v),
-            Err(e) => {
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(ErrorResponse {
-                        error: format!("invalid base64 in a: {e}"),
-                    }),
-                )
-                    .into_response();
-            }
+            Err(e) => return bad_request(format!("invalid base64 in a: {e}")),
         }
     }
 
@@ -126,28 +270,20 @@ async fn matrix(
     for s in &req.b {
         match decode_b64(s) {
             Ok(v) => b_vecs.push(v),
-            Err(e) => {
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(ErrorResponse {
-                        error: format!("invalid base64 in b: {e}"),
-                    }),
-                )
-                    .into_response();
-            }
+            Err(e) => return bad_request(format!("invalid base64 in b: {e}")),
         }
     }
 
-    let c = ncdprime_core::Gzip::new(req.gzip_level);
-    match ncdprime_core::ncd_matrix(&c, &a_vecs, &b_vecs, ncdprime_core::NcdOptions::default()) {
+    let spec = match compressor_spec(req.compressor.as_ref(), req.gzip_level) {
+        Ok(s) => s,
+        Err(e) => return bad_request(e),
+    };
+    let c = spec.build();
+    match st.with_cache(&*c, &spec, |c| {
+        ncdprime_core::ncd_matrix(c, &a_vecs, &b_vecs, opts)
+    }) {
         Ok(values) => (StatusCode::OK, Json(MatrixResponse { values })).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: e.to_string(),
-            }),
-        )
-            .into_response(),
+        Err(e) => ncd_error(e),
     }
 }
 
@@ -157,10 +293,24 @@ async fn main() -> anyhow::Result<()> {
         .with_env_filter(EnvFilter::from_default_env())
         .init();
 
-    let state = Arc::new(AppState {});
+    let cache = match std::env::var_os("NCDPRIME_CACHE") {
+        Some(path) => {
+            let max_entries = std::env::var("NCDPRIME_CACHE_MAX_ENTRIES")
+                .ok()
+                .map(|v| v.parse())
+                .transpose()?;
+            let cache = ncdprime_core::SizeCache::open(path, max_entries)?;
+            tracing::info!("size cache: {}", cache.path().display());
+            Some(cache)
+        }
+        None => None,
+    };
+
+    let state = Arc::new(AppState { cache });
 
     let app = Router::new()
         .route("/health", get(health))
+        .route("/compressors", get(compressors))
         .route("/ncd/pair", post(pair))
         .route("/ncd/matrix", post(matrix))
         .with_state(state);
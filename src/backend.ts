@@ -1,8 +1,28 @@
 import { createRequire } from "node:module";
 
+/** Integer compressor parameters; `compressors()` lists which each id takes. */
+export type NativeCompressorParams = Partial<
+  Record<"level" | "quality" | "q" | "lgwin" | "accel" | "hc" | "order" | "o" | "mem", number>
+>;
+
+export type NativeNcdOptions = {
+  join?: "frame64" | "concat" | `sep${string}` | `interleave${string}`;
+  symmetry?: "none" | "min" | "avg" | "max";
+  metric?: "ncd" | "cdm" | "clm";
+  clamp?: boolean;
+  /** Compressor spec, e.g. "zstd", "zstd:level=19" or { id: "zstd", level: 19 }; overrides gzipLevel. */
+  compressor?: string | ({ id: string } & NativeCompressorParams);
+};
+
 export type NativeBinding = {
-  ncd: (x: Uint8Array, y: Uint8Array, gzipLevel?: number) => number;
-  matrix: (a: Array<Uint8Array>, b: Array<Uint8Array>, gzipLevel?: number) => number[][];
+  ncd: (x: Uint8Array, y: Uint8Array, gzipLevel?: number, opts?: NativeNcdOptions) => number;
+  matrix: (
+    a: Array<Uint8Array>,
+    b: Array<Uint8Array>,
+    gzipLevel?: number,
+    opts?: NativeNcdOptions,
+  ) => number[][];
+  compressors: () => Array<{ id: string; params: Record<string, number> }>;
 };
 
 /**